@@ -0,0 +1,513 @@
+use std::collections::{HashMap, hash_map::Entry};
+use crate::{location::{path::FilePath, position::{Located, Position}}, error::Error, parser::ast::*};
+use super::{symbol::{Symbol, Resolution}, resolver::at};
+
+// code parameters belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Callable {
+    Procedure(usize, usize),
+    Function(usize, usize),
+    Event(usize, usize),
+}
+
+// anything holding a value whose type gets inferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    // a global or actor variable
+    Variable(Symbol),
+    Param(Callable, usize),
+    // the return value of a function
+    Return(usize, usize),
+    // a parameter of a message declaration
+    Message(Symbol, usize),
+    // the value handlers of a message reply to a request with
+    Reply(Symbol),
+}
+
+// every statement of a block, including the ones in nested blocks
+pub fn statements(block: &Block) -> Vec<&Located<Statement>> {
+    let mut statements = vec![];
+    for stat in &block.statements {
+        statements.push(stat);
+        match &stat.item {
+            Statement::If(_, case) | Statement::While(_, case) | Statement::Repeat(_, case) | Statement::Loop(case) => statements.append(&mut self::statements(&case.item)),
+            Statement::IfElse(_, case, else_case) => {
+                statements.append(&mut self::statements(&case.item));
+                statements.append(&mut self::statements(&else_case.item));
+            }
+            _ => {}
+        }
+    }
+    statements
+}
+// the word of a statement that only a thread can run, as it pauses the thread or sends mail
+pub fn suspends(stat: &Statement) -> Option<&'static str> {
+    match stat {
+        Statement::Wait(_) => Some("wait"),
+        Statement::Broadcast(_, _) => Some("broadcast"),
+        Statement::BroadcastWait(_, _) => Some("broadcast_wait"),
+        Statement::Request(_, _, _, _) => Some("request"),
+        Statement::Clone(_) => Some("clone"),
+        Statement::DeleteClone => Some("delete_clone"),
+        _ => None
+    }
+}
+
+// infers the types of variables, parameters and function returns and checks every use of them
+pub struct Checker<'a> {
+    pub path: FilePath,
+    program: &'a Program,
+    resolution: &'a Resolution,
+    types: HashMap<Slot, Type>,
+    // code currently being checked
+    callable: Option<Callable>,
+    // set when a pass inferred a new type
+    changed: bool,
+    // errors are only reported in the last pass, once every type that can be inferred is known
+    report: bool,
+    errors: Vec<Error>
+}
+impl<'a> Checker<'a> {
+    pub fn new(path: FilePath, program: &'a Program, resolution: &'a Resolution) -> Self {
+        Self { path, program, resolution, types: HashMap::new(), callable: None, changed: false, report: false, errors: vec![] }
+    }
+    pub fn error<S: ToString>(&mut self, msg: S, pos: &Position) {
+        if self.report {
+            self.errors.push(Error::new(msg, self.path.clone(), Some(pos.clone())));
+        }
+    }
+    pub fn mismatch(&mut self, expected: Type, found: Type, pos: &Position) {
+        self.error(format!("expected {expected}, got {found}"), pos);
+    }
+
+    pub fn slot(&self, pos: &Position) -> Option<Slot> {
+        match self.resolution.get(pos)? {
+            Symbol::Param(idx) => Some(Slot::Param(self.callable?, idx)),
+            symbol @ (Symbol::Global(_) | Symbol::Local(_, _)) => Some(Slot::Variable(symbol)),
+            _ => None
+        }
+    }
+    // the variable an expression names, `x` and `(x)` both name `x`
+    pub fn variable(&self, expr: &Located<Expression>) -> Option<Slot> {
+        match &expr.item {
+            Expression::ID(_) => self.slot(&expr.pos),
+            Expression::Call(id, _) => self.slot(&id.pos),
+            _ => None
+        }
+    }
+    pub fn message(&self, symbol: Symbol) -> Option<&'a Message> {
+        let program = self.program;
+        match symbol {
+            Symbol::Message(Some(actor), idx) => Some(&program.actors[actor].item.messages[idx].item),
+            Symbol::Message(None, idx) => Some(&program.messages[idx].item),
+            _ => None
+        }
+    }
+    // what a `[return]` in the current code gives its value to
+    pub fn returns(&self) -> Option<Slot> {
+        match self.callable? {
+            Callable::Function(actor, func) => Some(Slot::Return(actor, func)),
+            Callable::Event(actor, event) => match &self.program.actors[actor].item.events[event].item.trigger.item {
+                Trigger::Message(id, _) => Some(Slot::Reply(self.resolution.get(&id.pos)?)),
+                _ => None
+            }
+            Callable::Procedure(_, _) => None
+        }
+    }
+    pub fn infer(&mut self, slot: Slot, typ: Option<Type>) {
+        let Some(typ) = typ else { return };
+        if let Entry::Vacant(entry) = self.types.entry(slot) {
+            entry.insert(typ);
+            self.changed = true;
+        }
+    }
+    // checks an expression against a type, an identifier of unknown type gets that type
+    pub fn expect(&mut self, expr: &Located<Expression>, typ: Type) {
+        match self.expression(expr) {
+            Some(found) if found != typ => self.mismatch(typ, found, &expr.pos),
+            Some(_) => {}
+            None => if let Some(slot) = self.variable(expr) {
+                self.infer(slot, Some(typ));
+            }
+        }
+    }
+    // checks an expression against a slot, inferring whichever of both is unknown
+    pub fn assign(&mut self, slot: Slot, expr: &Located<Expression>) {
+        match self.types.get(&slot).copied() {
+            Some(typ) => self.expect(expr, typ),
+            None => {
+                let found = self.expression(expr);
+                self.infer(slot, found);
+            }
+        }
+    }
+    // gives both slots the same type
+    pub fn link(&mut self, slot: Slot, other: Slot, pos: &Position) {
+        match (self.types.get(&slot).copied(), self.types.get(&other).copied()) {
+            (Some(typ), Some(other)) => if typ != other {
+                self.mismatch(typ, other, pos);
+            }
+            (Some(typ), None) => self.infer(other, Some(typ)),
+            (None, typ) => self.infer(slot, typ),
+        }
+    }
+    pub fn assign_bool(&mut self, slot: Slot, pos: &Position) {
+        match self.types.get(&slot).copied() {
+            Some(Type::Bool) => {}
+            Some(typ) => self.mismatch(typ, Type::Bool, pos),
+            None => self.infer(slot, Some(Type::Bool))
+        }
+    }
+
+    pub fn expression(&mut self, expr: &Located<Expression>) -> Option<Type> {
+        match &expr.item {
+            Expression::ID(_) => self.variable(expr).and_then(|slot| self.types.get(&slot).copied()),
+            Expression::Number(_) => Some(Type::Number),
+            Expression::String(_) => Some(Type::String),
+            Expression::Add(left, right) | Expression::Sub(left, right) | Expression::Mul(left, right) |
+            Expression::Div(left, right) | Expression::Pow(left, right) | Expression::Mod(left, right) |
+            Expression::Max(left, right) | Expression::Min(left, right) => {
+                self.expect(left, Type::Number);
+                self.expect(right, Type::Number);
+                Some(Type::Number)
+            }
+            Expression::Join(left, right) => {
+                self.expect(left, Type::String);
+                self.expect(right, Type::String);
+                Some(Type::String)
+            }
+            Expression::Index(string, idx) => {
+                self.expect(string, Type::String);
+                self.expect(idx, Type::Number);
+                Some(Type::String)
+            }
+            Expression::SubString(string, start, end) => {
+                self.expect(string, Type::String);
+                self.expect(start, Type::Number);
+                self.expect(end, Type::Number);
+                Some(Type::String)
+            }
+            Expression::Contains(string, part) => {
+                self.expect(string, Type::String);
+                self.expect(part, Type::String);
+                Some(Type::Bool)
+            }
+            Expression::Call(id, args) => {
+                if let Some(slot) = self.variable(expr) {
+                    return self.types.get(&slot).copied()
+                }
+                let Some(Symbol::Function(actor, func)) = self.resolution.get(&id.pos) else { return None };
+                let function = &self.program.actors[actor].item.functions[func].item;
+                self.call(id, &function.params, args, &expr.pos, |idx| Slot::Param(Callable::Function(actor, func), idx));
+                self.types.get(&Slot::Return(actor, func)).copied()
+            }
+        }
+    }
+    // both sides of a comparison have to be of the same type
+    pub fn compare(&mut self, left: &Located<Expression>, right: &Located<Expression>) -> Option<Type> {
+        let typ = match (self.expression(left), self.expression(right)) {
+            (Some(typ), Some(other)) => {
+                if typ != other {
+                    self.mismatch(typ, other, &right.pos);
+                }
+                typ
+            }
+            (Some(typ), None) => {
+                self.expect(right, typ);
+                typ
+            }
+            (None, Some(typ)) => {
+                self.expect(left, typ);
+                typ
+            }
+            (None, None) => return None
+        };
+        Some(typ)
+    }
+    pub fn condition(&mut self, cond: &Located<Condition>) {
+        match &cond.item {
+            Condition::True | Condition::False => {}
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                self.condition(left);
+                self.condition(right);
+            }
+            Condition::Not(cond) => self.condition(cond),
+            Condition::EQ(left, right) | Condition::NE(left, right) => {
+                self.compare(left, right);
+            }
+            Condition::LT(left, right) | Condition::GT(left, right) | Condition::LE(left, right) | Condition::GE(left, right) => {
+                if let Some(Type::Bool) = self.compare(left, right) {
+                    self.error("can't order bools", &cond.pos);
+                }
+            }
+            Condition::ID(_) => if let Some(slot) = self.slot(&cond.pos) {
+                self.assign_bool(slot, &cond.pos);
+            }
+            Condition::Call(id, args) => {
+                if let Some(slot) = self.slot(&id.pos) {
+                    return self.assign_bool(slot, &cond.pos)
+                }
+                let Some(Symbol::Function(actor, func)) = self.resolution.get(&id.pos) else { return };
+                let function = &self.program.actors[actor].item.functions[func].item;
+                self.call(id, &function.params, args, &cond.pos, |idx| Slot::Param(Callable::Function(actor, func), idx));
+                if let Some(typ) = self.types.get(&Slot::Return(actor, func)).copied().filter(|typ| typ != &Type::Bool) {
+                    self.mismatch(Type::Bool, typ, &cond.pos);
+                }
+            }
+        }
+    }
+    // checks the number of arguments and their types against the parameters
+    pub fn call(&mut self, id: &Located<String>, params: &[Located<Param>], args: &[Argument], pos: &Position, slot: impl Fn(usize) -> Slot) {
+        let required = params.iter().rposition(|param| param.item.default.is_none()).map(|idx| idx + 1).unwrap_or(0);
+        if args.len() < required || args.len() > params.len() {
+            let expected = if required == params.len() { format!("{required}") } else { format!("{required} to {}", params.len()) };
+            self.error(format!("{:?} expects {expected} arguments, got {}", id.item, args.len()), pos);
+        }
+        for (idx, arg) in args.iter().enumerate() {
+            let slot = slot(idx);
+            match arg {
+                Argument::Expression(expr) => {
+                    if idx < params.len() {
+                        self.assign(slot, expr);
+                    } else {
+                        self.expression(expr);
+                    }
+                }
+                Argument::Condition(cond) => {
+                    self.condition(cond);
+                    if idx < params.len() {
+                        self.assign_bool(slot, &cond.pos);
+                    }
+                }
+            }
+        }
+    }
+    pub fn statement(&mut self, stat: &Located<Statement>) {
+        match &stat.item {
+            Statement::Set(id, expr) => match self.slot(&id.pos) {
+                Some(slot) => self.assign(slot, expr),
+                None => {
+                    self.expression(expr);
+                }
+            }
+            Statement::SetCondition(id, cond) => {
+                self.condition(cond);
+                if let Some(slot) = self.slot(&id.pos) {
+                    self.assign_bool(slot, &cond.pos);
+                }
+            }
+            Statement::If(cond, case) | Statement::While(cond, case) => {
+                self.condition(cond);
+                self.block(case);
+            }
+            Statement::IfElse(cond, case, else_case) => {
+                self.condition(cond);
+                self.block(case);
+                self.block(else_case);
+            }
+            Statement::Repeat(count, body) => {
+                self.expect(count, Type::Number);
+                self.block(body);
+            }
+            Statement::Loop(body) => self.block(body),
+            Statement::Wait(ticks) => self.expect(ticks, Type::Number),
+            Statement::Break | Statement::Next | Statement::Clone(_) | Statement::DeleteClone | Statement::Error => {}
+            Statement::Return(expr) => match self.returns() {
+                Some(slot) => self.assign(slot, expr),
+                None => {
+                    self.expression(expr);
+                }
+            }
+            Statement::ReturnCondition(cond) => {
+                self.condition(cond);
+                if let Some(slot) = self.returns() {
+                    self.assign_bool(slot, &cond.pos);
+                }
+            }
+            Statement::Call(id, args) => {
+                let Some(Symbol::Procedure(actor, proc)) = self.resolution.get(&id.pos) else { return };
+                let procedure = &self.program.actors[actor].item.procedures[proc].item;
+                self.call(id, &procedure.params, args, &stat.pos, |idx| Slot::Param(Callable::Procedure(actor, proc), idx));
+            }
+            Statement::Broadcast(id, args) | Statement::BroadcastWait(id, args) => {
+                let Some(symbol) = self.resolution.get(&id.pos) else { return };
+                let Some(message) = self.message(symbol) else { return };
+                self.call(id, &message.params, args, &stat.pos, |idx| Slot::Message(symbol, idx));
+            }
+            Statement::Request(var, id, args, timeout) => {
+                if let Some(timeout) = timeout {
+                    self.expect(timeout, Type::Number);
+                }
+                let Some(symbol) = self.resolution.get(&id.pos) else { return };
+                let Some(message) = self.message(symbol) else { return };
+                self.call(id, &message.params, args, &stat.pos, |idx| Slot::Message(symbol, idx));
+                if let Some(slot) = self.slot(&var.pos) {
+                    self.link(slot, Slot::Reply(symbol), &var.pos);
+                }
+            }
+        }
+    }
+    pub fn block(&mut self, block: &Located<Block>) {
+        for stat in &block.item.statements {
+            self.statement(stat);
+        }
+    }
+
+    // parameter types come from annotations and defaults
+    pub fn params(&mut self, callable: Callable, params: &[Located<Param>]) {
+        for (idx, Located { item: param, pos: _ }) in params.iter().enumerate() {
+            let slot = Slot::Param(callable, idx);
+            if param.typ == ValueType::Condition {
+                if let Some(annotation) = param.annotation.as_ref().filter(|annotation| annotation.item != Type::Bool) {
+                    self.mismatch(Type::Bool, annotation.item, &annotation.pos);
+                }
+                self.infer(slot, Some(Type::Bool));
+            }
+            if let Some(annotation) = &param.annotation {
+                self.infer(slot, Some(annotation.item));
+            }
+            match &param.default {
+                Some(Argument::Expression(expr)) => self.assign(slot, expr),
+                Some(Argument::Condition(cond)) => {
+                    self.condition(cond);
+                    self.assign_bool(slot, &cond.pos);
+                }
+                None => {}
+            }
+        }
+    }
+    pub fn actor(&mut self, idx: usize) {
+        let program = self.program;
+        let actor = &program.actors[idx].item;
+        self.callable = None;
+        for (var, Located { item: Variable { id: _, value }, pos: _ }) in actor.variables.iter().enumerate() {
+            let Some(value) = value else { continue };
+            self.assign(Slot::Variable(Symbol::Local(idx, var)), value);
+        }
+        for (proc, procedure) in actor.procedures.iter().enumerate() {
+            let callable = Callable::Procedure(idx, proc);
+            self.callable = None;
+            self.params(callable, &procedure.item.params);
+            self.callable = Some(callable);
+            self.block(&procedure.item.body);
+        }
+        for (func, function) in actor.functions.iter().enumerate() {
+            let callable = Callable::Function(idx, func);
+            self.callable = None;
+            self.params(callable, &function.item.params);
+            if function.item.return_type == ValueType::Condition {
+                self.infer(Slot::Return(idx, func), Some(Type::Bool));
+            }
+            self.callable = Some(callable);
+            self.block(&function.item.body);
+        }
+        for (event, Located { item: Event { trigger, body }, pos: _ }) in actor.events.iter().enumerate() {
+            let callable = Callable::Event(idx, event);
+            self.callable = Some(callable);
+            match &trigger.item {
+                Trigger::Start | Trigger::Clone => {}
+                // bindings take the types of the message's parameters
+                Trigger::Message(id, bindings) => if let Some((symbol, message)) = self.resolution.get(&id.pos).and_then(|symbol| Some((symbol, self.message(symbol)?))) {
+                    if bindings.len() != message.params.len() {
+                        self.error(format!("{:?} has {} parameters, got {} bindings", id.item, message.params.len(), bindings.len()), &trigger.pos);
+                    }
+                    for (binding, Located { item: _, pos }) in bindings.iter().enumerate().take(message.params.len()) {
+                        self.link(Slot::Param(callable, binding), Slot::Message(symbol, binding), pos);
+                    }
+                }
+                Trigger::Condition(cond) => self.condition(cond),
+            }
+            self.block(body);
+        }
+        self.callable = None;
+    }
+    // message parameter types come from annotations
+    pub fn messages(&mut self, messages: &[Located<Message>], symbol: impl Fn(usize) -> Symbol) {
+        for (idx, message) in messages.iter().enumerate() {
+            for (param, Located { item: Param { annotation, .. }, pos: _ }) in message.item.params.iter().enumerate() {
+                let Some(annotation) = annotation else { continue };
+                self.infer(Slot::Message(symbol(idx), param), Some(annotation.item));
+            }
+        }
+    }
+    pub fn pass(&mut self) {
+        let program = self.program;
+        self.messages(&program.messages, |idx| Symbol::Message(None, idx));
+        for (actor, Located { item: Actor { messages, .. }, pos: _ }) in program.actors.iter().enumerate() {
+            self.messages(messages, |idx| Symbol::Message(Some(actor), idx));
+        }
+        for (var, Located { item: Variable { id: _, value }, pos: _ }) in program.variables.iter().enumerate() {
+            let Some(value) = value else { continue };
+            self.assign(Slot::Variable(Symbol::Global(var)), value);
+        }
+        for idx in 0..program.actors.len() {
+            self.actor(idx);
+        }
+    }
+    // the statement every procedure only a thread can run reaches first, directly or through other procedures
+    pub fn suspending(&self) -> HashMap<(usize, usize), (&'static str, Position)> {
+        let program = self.program;
+        let mut suspending = HashMap::new();
+        loop {
+            let mut changed = false;
+            for (actor, Located { item: Actor { procedures, .. }, pos: _ }) in program.actors.iter().enumerate() {
+                for (proc, procedure) in procedures.iter().enumerate() {
+                    if suspending.contains_key(&(actor, proc)) { continue; }
+                    let reached = statements(&procedure.item.body.item).into_iter().find_map(|stat| match &stat.item {
+                        Statement::Call(id, _) => match self.resolution.get(&id.pos) {
+                            Some(Symbol::Procedure(actor, proc)) => suspending.get(&(actor, proc)).cloned(),
+                            _ => None
+                        }
+                        stat_item => suspends(stat_item).map(|word| (word, stat.pos.clone()))
+                    });
+                    if let Some(reached) = reached {
+                        suspending.insert((actor, proc), reached);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed { break; }
+        }
+        suspending
+    }
+    // functions run to the end at once, so they can't call procedures that pause or send mail
+    pub fn calls_in_functions(&mut self) {
+        let suspending = self.suspending();
+        for actor in &self.program.actors {
+            for function in &actor.item.functions {
+                for stat in statements(&function.item.body.item) {
+                    let Statement::Call(id, _) = &stat.item else { continue };
+                    let Some(Symbol::Procedure(actor, proc)) = self.resolution.get(&id.pos) else { continue };
+                    let Some((word, pos)) = suspending.get(&(actor, proc)) else { continue };
+                    self.error(format!("{:?} can't be called inside of a function, it reaches {word:?} at {}", id.item, at(pos)), &stat.pos);
+                }
+            }
+        }
+    }
+    // passes until no more types can be inferred
+    pub fn infer_all(&mut self) {
+        loop {
+            self.changed = false;
+            self.pass();
+            if !self.changed { break; }
+        }
+    }
+    pub fn check(mut self) -> (HashMap<Slot, Type>, Vec<Error>) {
+        self.infer_all();
+        // variables that are never given a value hold 0
+        let program = self.program;
+        for var in 0..program.variables.len() {
+            self.infer(Slot::Variable(Symbol::Global(var)), Some(Type::Number));
+        }
+        for (idx, actor) in program.actors.iter().enumerate() {
+            for var in 0..actor.item.variables.len() {
+                self.infer(Slot::Variable(Symbol::Local(idx, var)), Some(Type::Number));
+            }
+        }
+        self.infer_all();
+        self.report = true;
+        self.pass();
+        self.calls_in_functions();
+        (self.types, self.errors)
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use crate::{location::{path::FilePath, position::Located}, parser::ast::{Program, Type}, error::Error};
+
+pub mod symbol;
+pub mod resolver;
+pub mod checker;
+
+use resolver::Resolver;
+use symbol::Resolution;
+use checker::{Checker, Slot};
+
+pub fn resolve(path: &FilePath, program: &Located<Program>) -> Result<Resolution, Vec<Error>> {
+    let (resolution, errors) = Resolver::new(path.clone(), &program.item).resolve();
+    if !errors.is_empty() {
+        return Err(sorted(errors))
+    }
+    Ok(resolution)
+}
+
+pub fn check(path: &FilePath, program: &Located<Program>, resolution: &Resolution) -> Result<HashMap<Slot, Type>, Vec<Error>> {
+    let (types, errors) = Checker::new(path.clone(), &program.item, resolution).check();
+    if !errors.is_empty() {
+        return Err(sorted(errors))
+    }
+    Ok(types)
+}
+
+// diagnostics are found item by item, they are reported in source order
+fn sorted(mut errors: Vec<Error>) -> Vec<Error> {
+    errors.sort_by_key(|err| err.pos().map(|pos| pos.idx.start));
+    errors
+}
+
+#[cfg(test)]
+mod tests;
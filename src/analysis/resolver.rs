@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use crate::{location::{path::FilePath, position::{Located, Position}}, error::Error, parser::ast::*};
+use super::symbol::{Symbol, Resolution};
+
+// "ln:col" for pointing at another declaration in a message
+pub fn at(pos: &Position) -> String {
+    format!("{}:{}", pos.ln.start + 1, pos.col.start + 1)
+}
+
+// resolves every identifier of a program to its declaration
+pub struct Resolver<'a> {
+    pub path: FilePath,
+    program: &'a Program,
+    // actor currently being resolved
+    actor: usize,
+    // parameters of the surrounding procedure, function or event
+    params: Vec<Located<String>>,
+    // while resolving an actor variable's initializer, the number of actor variables declared before it
+    declared: Option<usize>,
+    resolution: Resolution,
+    errors: Vec<Error>
+}
+impl<'a> Resolver<'a> {
+    pub fn new(path: FilePath, program: &'a Program) -> Self {
+        Self { path, program, actor: 0, params: vec![], declared: None, resolution: Resolution::new(), errors: vec![] }
+    }
+    pub fn error<S: ToString>(&mut self, msg: S, pos: &Position) {
+        self.errors.push(Error::new(msg, self.path.clone(), Some(pos.clone())));
+    }
+
+    // reports every name declared more than once
+    pub fn duplicates<'b>(&mut self, ids: impl Iterator<Item = &'b Located<String>>, what: &str) {
+        let mut declared: HashMap<&String, &Position> = HashMap::new();
+        for id in ids {
+            if let Some(pos) = declared.get(&id.item) {
+                let msg = format!("duplicate {what} {:?}, first declared at {}", id.item, at(pos));
+                self.error(msg, &id.pos);
+            } else {
+                declared.insert(&id.item, &id.pos);
+            }
+        }
+    }
+    // reports every name of `ids` hiding one of `outer`
+    pub fn shadowing<'b>(&mut self, ids: impl Iterator<Item = &'b Located<String>>, outer: &[Located<String>], what: &str) {
+        for id in ids {
+            if let Some(shadowed) = outer.iter().find(|outer| outer.item == id.item) {
+                let msg = format!("{:?} shadows the {what} declared at {}", id.item, at(&shadowed.pos));
+                self.error(msg, &id.pos);
+            }
+        }
+    }
+
+    pub fn variable(&self, id: &str) -> Option<Symbol> {
+        if let Some(idx) = self.params.iter().rposition(|param| param.item == id) {
+            return Some(Symbol::Param(idx))
+        }
+        let actor = &self.program.actors[self.actor].item;
+        let locals = &actor.variables[..self.declared.unwrap_or(actor.variables.len())];
+        if let Some(idx) = locals.iter().position(|var| var.item.id.item == id) {
+            return Some(Symbol::Local(self.actor, idx))
+        }
+        self.program.variables.iter().position(|var| var.item.id.item == id).map(Symbol::Global)
+    }
+    // the actor's own messages come first, then global ones and then the messages of other actors,
+    // which are public but have to be declared by a single actor to be found
+    pub fn message(&self, id: &str) -> Result<Symbol, String> {
+        let actor = &self.program.actors[self.actor].item;
+        if let Some(idx) = actor.messages.iter().position(|message| message.item.label() == id) {
+            return Ok(Symbol::Message(Some(self.actor), idx))
+        }
+        if let Some(idx) = self.program.messages.iter().position(|message| message.item.label() == id) {
+            return Ok(Symbol::Message(None, idx))
+        }
+        let declared = self.program.actors.iter().enumerate()
+            .filter_map(|(actor, Located { item: Actor { messages, .. }, pos: _ })| Some((actor, messages.iter().position(|message| message.item.label() == id)?)))
+            .collect::<Vec<(usize, usize)>>();
+        match declared[..] {
+            [(actor, idx)] => Ok(Symbol::Message(Some(actor), idx)),
+            [] => Err(format!("undeclared message {id:?}")),
+            _ => {
+                let actors = declared.iter().map(|&(actor, _)| format!("{:?}", self.program.actors[actor].item.id.item)).collect::<Vec<String>>();
+                Err(format!("ambiguous message {id:?}, declared by the actors {}", actors.join(", ")))
+            }
+        }
+    }
+
+    // variables inside of actors, remembering which actors use globals
+    pub fn insert(&mut self, pos: &Position, symbol: Symbol) {
+        if let Symbol::Global(_) = symbol {
+            self.resolution.share(self.actor);
+        }
+        self.resolution.insert(pos, symbol);
+    }
+    pub fn resolve_variable(&mut self, id: &str, pos: &Position) {
+        match self.variable(id) {
+            Some(symbol) => self.insert(pos, symbol),
+            None => self.error(format!("undefined variable {id:?}"), pos)
+        }
+    }
+    pub fn function(&self, id: &str) -> Option<Symbol> {
+        let actor = &self.program.actors[self.actor].item;
+        actor.functions.iter().position(|func| func.item.id.item == id).map(|idx| Symbol::Function(self.actor, idx))
+    }
+    pub fn resolve_function(&mut self, id: &Located<String>) {
+        match self.function(&id.item) {
+            Some(symbol) => self.resolution.insert(&id.pos, symbol),
+            None => self.error(format!("undefined function {:?}", id.item), &id.pos)
+        }
+    }
+    // `(x)` and `<x>` are the variable `x` in brackets, unless the actor has a function called `x`
+    pub fn resolve_call(&mut self, id: &Located<String>, args: &[Argument]) {
+        if args.is_empty() && self.function(&id.item).is_none() && self.variable(&id.item).is_some() {
+            return self.resolve_variable(&id.item, &id.pos)
+        }
+        self.resolve_function(id);
+        self.arguments(args);
+    }
+    pub fn resolve_procedure(&mut self, id: &Located<String>) {
+        let actor = &self.program.actors[self.actor].item;
+        match actor.procedures.iter().position(|proc| proc.item.id.item == id.item) {
+            Some(idx) => self.resolution.insert(&id.pos, Symbol::Procedure(self.actor, idx)),
+            None => self.error(format!("undefined procedure {:?}", id.item), &id.pos)
+        }
+    }
+    pub fn resolve_actor(&mut self, id: &Located<String>) {
+        match self.program.actors.iter().position(|actor| actor.item.id.item == id.item) {
+            Some(idx) => {
+                self.resolution.insert(&id.pos, Symbol::Actor(idx));
+                self.resolution.copy(self.actor, idx);
+            }
+            None => self.error(format!("undefined actor {:?}", id.item), &id.pos)
+        }
+    }
+    pub fn resolve_message(&mut self, id: &Located<String>) {
+        match self.message(&id.item) {
+            Ok(symbol) => self.resolution.insert(&id.pos, symbol),
+            Err(msg) => self.error(msg, &id.pos)
+        }
+    }
+
+    pub fn expression(&mut self, expr: &Located<Expression>) {
+        match &expr.item {
+            Expression::ID(id) => self.resolve_variable(id, &expr.pos),
+            Expression::Number(_) | Expression::String(_) => {}
+            Expression::Add(left, right) | Expression::Sub(left, right) | Expression::Mul(left, right) |
+            Expression::Div(left, right) | Expression::Pow(left, right) | Expression::Mod(left, right) |
+            Expression::Max(left, right) | Expression::Min(left, right) | Expression::Join(left, right) |
+            Expression::Index(left, right) | Expression::Contains(left, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::SubString(string, start, end) => {
+                self.expression(string);
+                self.expression(start);
+                self.expression(end);
+            }
+            Expression::Call(id, args) => self.resolve_call(id, args),
+        }
+    }
+    pub fn condition(&mut self, cond: &Located<Condition>) {
+        match &cond.item {
+            Condition::True | Condition::False => {}
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                self.condition(left);
+                self.condition(right);
+            }
+            Condition::Not(cond) => self.condition(cond),
+            Condition::EQ(left, right) | Condition::NE(left, right) | Condition::LT(left, right) |
+            Condition::GT(left, right) | Condition::LE(left, right) | Condition::GE(left, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Condition::ID(id) => self.resolve_variable(id, &cond.pos),
+            Condition::Call(id, args) => self.resolve_call(id, args),
+        }
+    }
+    pub fn arguments(&mut self, args: &[Argument]) {
+        for arg in args {
+            match arg {
+                Argument::Expression(expr) => self.expression(expr),
+                Argument::Condition(cond) => self.condition(cond),
+            }
+        }
+    }
+    pub fn set(&mut self, id: &Located<String>) {
+        match self.variable(&id.item) {
+            Some(Symbol::Param(_)) => self.error(format!("can't set parameter {:?}", id.item), &id.pos),
+            Some(symbol) => self.insert(&id.pos, symbol),
+            None => self.error(format!("undefined variable {:?}", id.item), &id.pos)
+        }
+    }
+    pub fn statement(&mut self, stat: &Located<Statement>) {
+        match &stat.item {
+            Statement::Set(id, expr) => {
+                self.set(id);
+                self.expression(expr);
+            }
+            Statement::SetCondition(id, cond) => {
+                self.set(id);
+                self.condition(cond);
+            }
+            Statement::If(cond, case) | Statement::While(cond, case) => {
+                self.condition(cond);
+                self.block(case);
+            }
+            Statement::IfElse(cond, case, else_case) => {
+                self.condition(cond);
+                self.block(case);
+                self.block(else_case);
+            }
+            Statement::Repeat(count, body) => {
+                self.expression(count);
+                self.block(body);
+            }
+            Statement::Wait(ticks) => self.expression(ticks),
+            Statement::Broadcast(id, args) | Statement::BroadcastWait(id, args) => {
+                self.resolve_message(id);
+                self.arguments(args);
+            }
+            Statement::Request(var, id, args, timeout) => {
+                self.set(var);
+                self.resolve_message(id);
+                self.arguments(args);
+                if let Some(timeout) = timeout {
+                    self.expression(timeout);
+                }
+            }
+            Statement::Clone(id) => if let Some(id) = id {
+                self.resolve_actor(id);
+            }
+            Statement::Loop(body) => self.block(body),
+            Statement::Break | Statement::Next | Statement::DeleteClone | Statement::Error => {}
+            Statement::Return(expr) => self.expression(expr),
+            Statement::ReturnCondition(cond) => self.condition(cond),
+            Statement::Call(id, args) => {
+                self.resolve_procedure(id);
+                self.arguments(args);
+            }
+        }
+    }
+    pub fn block(&mut self, block: &Located<Block>) {
+        for stat in &block.item.statements {
+            self.statement(stat);
+        }
+    }
+
+    // parameter defaults are resolved before the parameters are in scope
+    pub fn params(&mut self, params: &[Located<Param>]) {
+        for param in params {
+            if let Some(default) = &param.item.default {
+                self.arguments(std::slice::from_ref(default));
+            }
+        }
+        let ids = params.iter().map(|param| param.item.id.clone()).collect::<Vec<Located<String>>>();
+        self.bind(ids, "parameter");
+    }
+    // puts parameters or message bindings in scope
+    pub fn bind(&mut self, ids: Vec<Located<String>>, what: &str) {
+        self.duplicates(ids.iter(), what);
+        let actor = &self.program.actors[self.actor].item;
+        let variables = actor.variables.iter().chain(self.program.variables.iter())
+            .map(|var| var.item.id.clone()).collect::<Vec<Located<String>>>();
+        self.shadowing(ids.iter(), &variables, "variable");
+        self.params = ids;
+    }
+    pub fn actor(&mut self, idx: usize) {
+        self.actor = idx;
+        let program = self.program;
+        let actor = &program.actors[idx].item;
+        let globals = program.variables.iter().map(|var| var.item.id.clone()).collect::<Vec<Located<String>>>();
+        self.duplicates(actor.variables.iter().map(|var| &var.item.id), "variable");
+        self.shadowing(actor.variables.iter().map(|var| &var.item.id), &globals, "global variable");
+        let messages = actor.messages.iter().map(|message| Located::new(message.item.label().clone(), message.pos.clone())).collect::<Vec<Located<String>>>();
+        let global_messages = program.messages.iter().map(|message| Located::new(message.item.label().clone(), message.pos.clone())).collect::<Vec<Located<String>>>();
+        self.duplicates(messages.iter(), "message");
+        self.shadowing(messages.iter(), &global_messages, "global message");
+        for message in &actor.messages {
+            self.duplicates(message.item.params.iter().map(|param| &param.item.id), "parameter");
+        }
+        self.duplicates(actor.procedures.iter().map(|proc| &proc.item.id), "procedure");
+        self.duplicates(actor.functions.iter().map(|func| &func.item.id), "function");
+
+        // like global initializers, actor initializers can only see actor variables declared before them
+        for (idx, var) in actor.variables.iter().enumerate() {
+            let Some(value) = &var.item.value else { continue };
+            self.declared = Some(idx);
+            self.expression(value);
+        }
+        self.declared = None;
+        for proc in &actor.procedures {
+            self.params(&proc.item.params);
+            self.block(&proc.item.body);
+            self.params.clear();
+        }
+        for func in &actor.functions {
+            self.params(&func.item.params);
+            self.block(&func.item.body);
+            self.params.clear();
+        }
+        for event in &actor.events {
+            match &event.item.trigger.item {
+                Trigger::Start | Trigger::Clone => {}
+                Trigger::Message(id, bindings) => {
+                    self.resolve_message(id);
+                    self.bind(bindings.clone(), "binding");
+                }
+                Trigger::Condition(cond) => self.condition(cond),
+            }
+            self.block(&event.item.body);
+            self.params.clear();
+        }
+    }
+    pub fn resolve(mut self) -> (Resolution, Vec<Error>) {
+        let program = self.program;
+        self.duplicates(program.variables.iter().map(|var| &var.item.id), "variable");
+        let messages = program.messages.iter().map(|message| Located::new(message.item.label().clone(), message.pos.clone())).collect::<Vec<Located<String>>>();
+        self.duplicates(messages.iter(), "message");
+        for message in &program.messages {
+            self.duplicates(message.item.params.iter().map(|param| &param.item.id), "parameter");
+        }
+        self.duplicates(program.actors.iter().map(|actor| &actor.item.id), "actor");
+        // global initializers can only see global variables declared before them
+        for (idx, var) in program.variables.iter().enumerate() {
+            let Some(value) = &var.item.value else { continue };
+            self.global_initializer(value, idx);
+        }
+        for idx in 0..program.actors.len() {
+            self.actor(idx);
+        }
+        (self.resolution, self.errors)
+    }
+    pub fn global_initializer(&mut self, expr: &Located<Expression>, declared: usize) {
+        match &expr.item {
+            Expression::ID(id) => match self.program.variables[..declared].iter().position(|var| &var.item.id.item == id) {
+                Some(idx) => self.resolution.insert(&expr.pos, Symbol::Global(idx)),
+                None => self.error(format!("undefined variable {id:?}"), &expr.pos)
+            }
+            Expression::Call(id, args) => match self.program.variables[..declared].iter().position(|var| var.item.id.item == id.item) {
+                Some(idx) if args.is_empty() => self.resolution.insert(&id.pos, Symbol::Global(idx)),
+                _ => self.error(format!("can't call {:?} outside of an actor", id.item), &id.pos)
+            }
+            Expression::Number(_) | Expression::String(_) => {}
+            Expression::Add(left, right) | Expression::Sub(left, right) | Expression::Mul(left, right) |
+            Expression::Div(left, right) | Expression::Pow(left, right) | Expression::Mod(left, right) |
+            Expression::Max(left, right) | Expression::Min(left, right) | Expression::Join(left, right) |
+            Expression::Index(left, right) | Expression::Contains(left, right) => {
+                self.global_initializer(left, declared);
+                self.global_initializer(right, declared);
+            }
+            Expression::SubString(string, start, end) => {
+                self.global_initializer(string, declared);
+                self.global_initializer(start, declared);
+                self.global_initializer(end, declared);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::{HashMap, HashSet, BTreeSet};
+use crate::location::position::Position;
+
+// what an identifier refers to, indices point into the `Program` the symbol was resolved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    // index into `Program::actors`
+    Actor(usize),
+    // index into `Program::variables`
+    Global(usize),
+    // actor and index into `Actor::variables`
+    Local(usize, usize),
+    // index into the parameters of the surrounding procedure, function or event
+    Param(usize),
+    // declaring actor, `None` for global messages, and index into its messages
+    Message(Option<usize>, usize),
+    // actor and index into `Actor::procedures`
+    Procedure(usize, usize),
+    // actor and index into `Actor::functions`
+    Function(usize, usize),
+}
+
+// every resolved identifier, keyed by the byte offset it starts at
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolution {
+    symbols: HashMap<usize, Symbol>,
+    // actors reading or writing global variables
+    shared: HashSet<usize>,
+    // actors cloning another actor with a copy of its variables, and the actor they clone
+    copies: BTreeSet<(usize, usize)>
+}
+impl Resolution {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn insert(&mut self, pos: &Position, symbol: Symbol) {
+        self.symbols.insert(pos.idx.start, symbol);
+    }
+    pub fn get(&self, pos: &Position) -> Option<Symbol> {
+        self.symbols.get(&pos.idx.start).copied()
+    }
+    pub fn share(&mut self, actor: usize) {
+        self.shared.insert(actor);
+    }
+    pub fn uses_globals(&self, actor: usize) -> bool {
+        self.shared.contains(&actor)
+    }
+    pub fn copy(&mut self, actor: usize, cloned: usize) {
+        self.copies.insert((actor, cloned));
+    }
+    pub fn copies(&self) -> impl Iterator<Item = &(usize, usize)> {
+        self.copies.iter()
+    }
+}
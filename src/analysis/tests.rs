@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use crate::{lexer, parser::{self, ast::Type}, location::path::FilePath, testing::located};
+use super::{checker::{Slot, Callable}, symbol::Symbol};
+
+// every error of resolving the program
+fn errors(text: &str) -> Vec<String> {
+    let path = FilePath::Input(text.to_string());
+    let program = parser::parse(&path, lexer::lex(&path, text.to_string()).unwrap()).unwrap();
+    match super::resolve(&path, &program) {
+        Ok(_) => vec![],
+        Err(errors) => errors.iter().map(located).collect()
+    }
+}
+// the inferred types and every error of checking the program
+fn check(text: &str) -> (HashMap<Slot, Type>, Vec<String>) {
+    let path = FilePath::Input(text.to_string());
+    let program = parser::parse(&path, lexer::lex(&path, text.to_string()).unwrap()).unwrap();
+    let resolution = super::resolve(&path, &program).unwrap();
+    match super::check(&path, &program, &resolution) {
+        Ok(types) => (types, vec![]),
+        Err(errors) => (HashMap::new(), errors.iter().map(located).collect())
+    }
+}
+
+#[test]
+fn undefined_names() {
+    assert_eq!(errors("actor A { events { |start| { [set x 1]; [p]; [wait (f)]; [broadcast m]; [clone B] } } }"), vec![
+        "1:35: undefined variable \"x\"",
+        "1:42: undefined procedure \"p\"",
+        "1:53: undefined function \"f\"",
+        "1:69: undeclared message \"m\"",
+        "1:80: undefined actor \"B\"",
+    ]);
+    // global initializers only see the globals declared before them
+    assert_eq!(errors("data { (x = y) (y = (add x 1)) (z = (f)) }"), vec![
+        "1:13: undefined variable \"y\"",
+        "1:38: can't call \"f\" outside of an actor",
+    ]);
+    // and actor initializers the actor variables declared before them
+    assert_eq!(errors("data { (g = 1) } actor A { data { (x = y) (y = g) (w = w) (z = (z)) } functions { #(z) (1) } }"), vec![
+        "1:40: undefined variable \"y\"",
+        "1:56: undefined variable \"w\"",
+    ]);
+}
+
+#[test]
+fn local_messages_are_public() {
+    assert_eq!(errors("actor A { messages { (ping) } } actor B { events { |start| { [broadcast ping] } |ping| {} } }"), Vec::<String>::new());
+    // but only if a single actor declares them
+    assert_eq!(errors("actor A { messages { (m) } } actor B { messages { (m) } events { |m| {} } } actor C { events { |start| { [broadcast m] } } }"), vec![
+        "1:117: ambiguous message \"m\", declared by the actors \"A\", \"B\"",
+    ]);
+}
+
+#[test]
+fn duplicate_names() {
+    assert_eq!(errors("data { (x) (x) } messages { (m) (m) } actor A {} actor A {}"), vec![
+        "1:13: duplicate variable \"x\", first declared at 1:9",
+        "1:33: duplicate message \"m\", first declared at 1:29",
+        "1:56: duplicate actor \"A\", first declared at 1:45",
+    ]);
+    assert_eq!(errors("actor A { data { (x) (x) } procedures { #p {} #p {} } functions { #(f) (1) #(f) (2) } messages { (m a a) } }"), vec![
+        "1:23: duplicate variable \"x\", first declared at 1:19",
+        "1:48: duplicate procedure \"p\", first declared at 1:42",
+        "1:78: duplicate function \"f\", first declared at 1:69",
+        "1:103: duplicate parameter \"a\", first declared at 1:101",
+    ]);
+}
+
+#[test]
+fn shadowing_names() {
+    assert_eq!(errors("data { (x) } messages { (m) } actor A { data { (x) } messages { (m) } procedures { #p (x) (y) (y) {} } events { |m| {} } }"), vec![
+        "1:49: \"x\" shadows the global variable declared at 1:9",
+        "1:65: \"m\" shadows the global message declared at 1:25",
+        "1:88: \"x\" shadows the variable declared at 1:49",
+        "1:96: duplicate parameter \"y\", first declared at 1:92",
+    ]);
+    // a binding shadowing a variable is still a parameter
+    assert_eq!(errors("data { (x) } messages { (m a) } actor A { events { |m x| { [set x 1] } } procedures { #p (a) { [set a 1] } } }"), vec![
+        "1:55: \"x\" shadows the variable declared at 1:9",
+        "1:65: can't set parameter \"x\"",
+        "1:101: can't set parameter \"a\"",
+    ]);
+}
+
+#[test]
+fn resolve_errors_are_in_source_order() {
+    // duplicate actors are found before the bodies of the actors are resolved
+    assert_eq!(errors("actor A { events { |start| { [set y 1] } } } actor A {}"), vec![
+        "1:35: undefined variable \"y\"",
+        "1:52: duplicate actor \"A\", first declared at 1:7",
+    ]);
+}
+
+#[test]
+fn bracketed_variables_are_not_calls() {
+    // `(x)` names the variable `x` unless there is a function `x`
+    assert_eq!(errors("data { (g = 1) (h = (g)) } actor A { data { (x) } functions { #(f) { [return x] } } events { |start| { [set x (add (x) (f))] [set x (y)] } } }"), vec![
+        "1:134: undefined function \"y\"",
+    ]);
+}
+
+#[test]
+fn types_are_inferred_from_initializers() {
+    let (types, errors) = check("data { (a = 1) (b = \"s\") (c) (d = a) } actor A { data { (e = (join b b)) } }");
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(types[&Slot::Variable(Symbol::Global(0))], Type::Number);
+    assert_eq!(types[&Slot::Variable(Symbol::Global(1))], Type::String);
+    // variables without a value hold 0
+    assert_eq!(types[&Slot::Variable(Symbol::Global(2))], Type::Number);
+    assert_eq!(types[&Slot::Variable(Symbol::Global(3))], Type::Number);
+    assert_eq!(types[&Slot::Variable(Symbol::Local(0, 0))], Type::String);
+}
+
+#[test]
+fn types_are_inferred_from_assignments() {
+    let (types, errors) = check("data { (s) } actor A { data { (b) (x) (y) } functions { #(f) (n) (add n 1) } \
+        events { |start| { [set s (join \"a\" \"b\")]; [set b <eq s \"ab\">]; [set x (f 2)]; [set y (index \"ab\" x)] } } }");
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(types[&Slot::Variable(Symbol::Global(0))], Type::String);
+    assert_eq!(types[&Slot::Variable(Symbol::Local(0, 0))], Type::Bool);
+    assert_eq!(types[&Slot::Variable(Symbol::Local(0, 1))], Type::Number);
+    assert_eq!(types[&Slot::Variable(Symbol::Local(0, 2))], Type::String);
+    // through the call of the function
+    assert_eq!(types[&Slot::Param(Callable::Function(0, 0), 0)], Type::Number);
+    assert_eq!(types[&Slot::Return(0, 0)], Type::Number);
+}
+
+#[test]
+fn type_mismatches() {
+    let (_, errors) = check("data { (x = 1) (b) } actor A { events { |start| { [set x \"s\"]; [set b true]; [wait b]; [set x (join x 1)] } } }");
+    assert_eq!(errors, vec![
+        "1:58: expected number, got string",
+        "1:84: expected number, got bool",
+        "1:95: expected number, got string",
+        "1:101: expected string, got number",
+        "1:103: expected string, got number",
+    ]);
+    let (_, errors) = check("data { (s = \"a\") (b) } actor A { events { |start| { [set b false]; [if <lt b b> {}]; [if <eq s 1> {}]; [repeat s {}] } } }");
+    assert_eq!(errors, vec!["1:72: can't order bools", "1:96: expected string, got number", "1:112: expected number, got string"]);
+    let (_, errors) = check("actor A { functions { #(f) (n: string = 1) (join n \"\") } procedures { #p (a) {} } events { |start| { [p 1 2]; [wait (f)] } } }");
+    assert_eq!(errors, vec!["1:41: expected string, got number", "1:102: \"p\" expects 1 arguments, got 2", "1:117: expected number, got string"]);
+}
+
+#[test]
+fn check_errors_are_in_source_order() {
+    // procedures are checked before events
+    let (_, errors) = check("actor A { events { |start| { [wait \"s\"] } } procedures { #p { [wait \"t\"] } } }");
+    assert_eq!(errors, vec!["1:36: expected number, got string", "1:69: expected number, got string"]);
+}
+
+#[test]
+fn functions_cant_call_procedures_that_suspend() {
+    let (_, errors) = check("messages { (m) } actor A { \
+        procedures { #wait_a_tick { [if true { [wait 0] }] } #outer { [set x 1] [wait_a_tick] } #send { [broadcast m] } #fine { [set x 2] } } \
+        functions { #(f) { [fine] [repeat 2 { [outer] }] [send] [return x] } #<g> { [if true { [wait_a_tick] }] [return true] } } \
+        data { (x) } events { |start| { [outer] [wait (f)] } } }");
+    assert_eq!(errors, vec![
+        "1:200: \"outer\" can't be called inside of a function, it reaches \"wait\" at 1:67",
+        "1:211: \"send\" can't be called inside of a function, it reaches \"broadcast\" at 1:124",
+        "1:249: \"wait_a_tick\" can't be called inside of a function, it reaches \"wait\" at 1:67",
+    ]);
+}
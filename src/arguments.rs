@@ -1,8 +1,8 @@
-use std::{collections::{HashSet, HashMap}, env::Args, fmt::Debug};
+use std::{collections::{HashSet, HashMap, VecDeque}, env::Args, fmt::Debug};
 
 pub type CheckSettingFn = fn(String) -> Result<String, String>;
 pub struct Arguments {
-    pub collected_input: Vec<String>,
+    pub collected_input: VecDeque<String>,
     pub collected_settings: HashMap<String, String>,
     pub collected_flags: HashSet<String>,
 
@@ -14,14 +14,14 @@ pub struct Arguments {
 impl Arguments {
     pub fn new() -> Self {
         Self {
-            collected_input: vec![], collected_settings: HashMap::new(), collected_flags: HashSet::new(),
+            collected_input: VecDeque::new(), collected_settings: HashMap::new(), collected_flags: HashSet::new(),
             settings: HashMap::new(), flags: HashSet::new(),
             check_settings: false, check_flags: false
         }
     }
     pub fn new_checked() -> Self {
         Self {
-            collected_input: vec![], collected_settings: HashMap::new(), collected_flags: HashSet::new(),
+            collected_input: VecDeque::new(), collected_settings: HashMap::new(), collected_flags: HashSet::new(),
             settings: HashMap::new(), flags: HashSet::new(),
             check_settings: true, check_flags: true
         }
@@ -85,20 +85,16 @@ impl Arguments {
         while let Some(arg) = args.next() {
             if arg.starts_with("--") {
                 let Some(flag) = arg.get(2..) else { continue; };
-                if self.check_flags {
-                    if !self.flags.contains(flag) {
-                        return Err(format!("invalid flag {flag:?}"))
-                    }
+                if self.check_flags && !self.flags.contains(flag) {
+                    return Err(format!("invalid flag {flag:?}"))
                 }
                 self.collected_flags.insert(flag.to_string());
                 continue;
             }
             if arg.starts_with("-") {
                 let Some(setting) = arg.get(1..) else { continue; };
-                if self.check_settings {
-                    if !self.settings.contains_key(setting) {
-                        return Err(format!("invalid setting {setting:?}"))
-                    }
+                if self.check_settings && !self.settings.contains_key(setting) {
+                    return Err(format!("invalid setting {setting:?}"))
                 }
                 let mut setting = setting.to_string();
                 if let Some(arg) = args.next() {
@@ -113,16 +109,16 @@ impl Arguments {
                 }
                 continue;
             }
-            self.collected_input.push(arg);
+            self.collected_input.push_back(arg);
         }
         Ok(())
     }
 
     pub fn next_input(&mut self) -> Option<String> {
-        if self.collected_input.len() > 0 { Some(self.collected_input.remove(0)) } else { None }
+        self.collected_input.pop_front()
     }
     pub fn next_input_ref(&self) -> Option<&String> {
-        self.collected_input.first()
+        self.collected_input.front()
     }
     pub fn get_flag<S: ToString>(&self, flag: S) -> bool {
         self.collected_flags.contains(&flag.to_string())
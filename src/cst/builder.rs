@@ -0,0 +1,127 @@
+use crate::{location::position::{Located, Position}, lexer::token::Token};
+use super::tree::*;
+
+// builds a `Cst` from the tokens of `Lexer::lex_recovering`,
+// everything between two tokens becomes trivia of the following token
+pub struct Builder {
+    text: String,
+    idx: usize, ln: usize, col: usize,
+    stack: Vec<Node>
+}
+impl Builder {
+    pub fn new(text: String) -> Self {
+        Self { text, idx: 0, ln: 0, col: 0, stack: vec![Node::new(NodeKind::Root)] }
+    }
+    pub fn get(&self) -> Option<char> {
+        self.text.get(self.idx..)?.chars().next()
+    }
+    pub fn pos(&self) -> Position {
+        let len = self.get().map(char::len_utf8).unwrap_or_default();
+        Position::new(self.idx..self.idx+len, self.ln..self.ln+1, self.col..self.col+1)
+    }
+    pub fn advance(&mut self) {
+        let Some(c) = self.get() else { return };
+        if c == '\n' {
+            self.ln += 1;
+            self.col = 0;
+        } else {
+            self.col += 1;
+        }
+        self.idx += c.len_utf8();
+    }
+    pub fn advance_to(&mut self, end: usize) {
+        while self.idx < end && self.get().is_some() {
+            self.advance();
+        }
+    }
+
+    // splits the text up to `end` into whitespace and comments
+    pub fn trivia(&mut self, end: usize) -> Vec<Trivia> {
+        let mut trivia = vec![];
+        while self.idx < end {
+            let start = self.idx;
+            let mut pos = self.pos();
+            let rest = &self.text[start..end];
+            let kind = if rest.starts_with("//") {
+                let len = rest.find('\n').unwrap_or(rest.len());
+                self.advance_to(start + len);
+                TriviaKind::LineComment
+            } else if rest.starts_with("/*") {
+                let mut depth = 0;
+                let mut len = rest.len();
+                let mut chars = rest.char_indices().peekable();
+                while let Some((i, c)) = chars.next() {
+                    match (c, chars.peek().map(|(_, c)| *c)) {
+                        ('/', Some('*')) => {
+                            depth += 1;
+                            chars.next();
+                        }
+                        ('*', Some('/')) => {
+                            depth -= 1;
+                            chars.next();
+                            if depth == 0 {
+                                len = i + 2;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                self.advance_to(start + len);
+                TriviaKind::BlockComment
+            } else {
+                let len = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len()).max(1);
+                self.advance_to(start + len);
+                TriviaKind::Whitespace
+            };
+            pos.extend(&Position::new(self.idx..self.idx, self.ln..self.ln+1, self.col..self.col));
+            trivia.push(Trivia::new(kind, self.text[start..self.idx].to_string(), pos));
+        }
+        trivia
+    }
+
+    pub fn push(&mut self, element: Element) {
+        self.stack.last_mut().expect("root node").children.push(element);
+    }
+    pub fn pop(&mut self) {
+        let node = self.stack.pop().expect("non-root node");
+        self.push(Element::Node(node));
+    }
+
+    pub fn token(&mut self, token: Located<Token>) {
+        let leading = self.trivia(token.pos.idx.start);
+        let text = self.text[token.pos.idx.clone()].to_string();
+        self.advance_to(token.pos.idx.end);
+        if let Some(kind) = NodeKind::open(&token.item) {
+            self.stack.push(Node::new(kind));
+            self.push(Element::Token(CstToken::new(leading, token, text)));
+            return
+        }
+        if let Some(kind) = NodeKind::close(&token.item) {
+            // close every unclosed node up to the matching one
+            if self.stack.iter().skip(1).any(|node| node.kind == kind) {
+                while self.stack.last().map(|node| node.kind) != Some(kind) {
+                    self.pop();
+                }
+                self.push(Element::Token(CstToken::new(leading, token, text)));
+                self.pop();
+            } else {
+                let mut node = Node::new(NodeKind::Error);
+                node.children.push(Element::Token(CstToken::new(leading, token, text)));
+                self.push(Element::Node(node));
+            }
+            return
+        }
+        self.push(Element::Token(CstToken::new(leading, token, text)));
+    }
+    pub fn build(mut self, tokens: Vec<Located<Token>>) -> Cst {
+        for token in tokens {
+            self.token(token);
+        }
+        let trailing = self.trivia(self.text.len());
+        while self.stack.len() > 1 {
+            self.pop();
+        }
+        Cst::new(self.stack.pop().expect("root node"), trailing)
+    }
+}
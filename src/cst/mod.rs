@@ -0,0 +1,16 @@
+use crate::{location::path::FilePath, lexer::lexer::Lexer, error::Error};
+
+pub mod tree;
+pub mod builder;
+
+use builder::Builder;
+use tree::Cst;
+
+// never fails, lexical errors are returned next to the tree
+pub fn build(path: &FilePath, text: String) -> (Cst, Vec<Error>) {
+    let (tokens, errors) = Lexer::new(path.clone(), text.clone()).lex_recovering();
+    (Builder::new(text).build(tokens), errors)
+}
+
+#[cfg(test)]
+mod tests;
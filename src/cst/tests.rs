@@ -0,0 +1,66 @@
+use crate::location::path::FilePath;
+use super::{build, tree::*};
+
+fn cst(text: &str) -> Cst {
+    build(&FilePath::Input(text.to_string()), text.to_string()).0
+}
+// printing the tree must give back the input byte for byte
+fn assert_round_trip(text: &str) {
+    assert_eq!(cst(text).to_string(), text);
+}
+
+#[test]
+fn round_trips_comments_and_whitespace() {
+    assert_round_trip("");
+    assert_round_trip("  \n\t ");
+    assert_round_trip("data { (x = 1) } // line comment\n");
+    assert_round_trip("// only a comment");
+    assert_round_trip("a /* outer /* nested */ still outer */ b\r\n  c");
+    assert_round_trip("actor A {\n    // note\n    events { |start| { [set x (add x 1)] } }\n}\n\n");
+}
+
+#[test]
+fn round_trips_unclosed_and_unmatched_input() {
+    assert_round_trip("(x = \"never closed");
+    assert_round_trip("a /* never /* closed */");
+    assert_round_trip("((a)");
+    assert_round_trip("a } ) b");
+    assert_round_trip("{ [ ( < }");
+}
+
+#[test]
+fn round_trips_multibyte_text() {
+    assert_round_trip("data { (grüße = \"héllo wörld 🦀\") } /* ✓ */");
+    assert_round_trip("é\u{301} ü");
+}
+
+#[test]
+fn round_trips_input_with_lex_errors() {
+    let text = "a @ $$ \"\\q\" 0x 1e b";
+    let (tree, errors) = build(&FilePath::Input(text.to_string()), text.to_string());
+    assert_eq!(tree.to_string(), text);
+    assert_eq!(errors.len(), 5);
+}
+
+#[test]
+fn comments_are_kept_as_trivia() {
+    let tree = cst("/* a */ x // b\n");
+    let Some(Element::Token(token)) = tree.root.children.first() else { panic!("expected a token") };
+    assert_eq!(token.leading.iter().map(|trivia| trivia.kind).collect::<Vec<_>>(), vec![TriviaKind::BlockComment, TriviaKind::Whitespace]);
+    assert_eq!(tree.trailing.iter().map(|trivia| trivia.kind).collect::<Vec<_>>(), vec![TriviaKind::Whitespace, TriviaKind::LineComment, TriviaKind::Whitespace]);
+}
+
+#[test]
+fn brackets_nest_into_nodes() {
+    let tree = cst("{ (a) } }");
+    let kinds = tree.root.children.iter().map(|child| match child {
+        Element::Node(node) => Some((node.kind, node.closed())),
+        Element::Token(_) => None
+    }).collect::<Vec<_>>();
+    // the stray `}` becomes an error node of its own
+    assert_eq!(kinds, vec![Some((NodeKind::Body, true)), Some((NodeKind::Error, false))]);
+    let tree = cst("{ (a");
+    let Some(Element::Node(body)) = tree.root.children.first() else { panic!("expected a node") };
+    assert!(!body.closed());
+    assert_eq!(body.tokens().len(), 3);
+}
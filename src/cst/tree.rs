@@ -0,0 +1,144 @@
+use std::fmt::Display;
+use crate::{location::position::{Located, Position}, lexer::token::Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace, LineComment, BlockComment
+}
+// source text between tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub pos: Position
+}
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: String, pos: Position) -> Self {
+        Self { kind, text, pos }
+    }
+}
+
+// a token with the exact source text it was lexed from and the trivia in front of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstToken {
+    pub leading: Vec<Trivia>,
+    pub token: Located<Token>,
+    pub text: String
+}
+impl CstToken {
+    pub fn new(leading: Vec<Trivia>, token: Located<Token>, text: String) -> Self {
+        Self { leading, token, text }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Root,
+    // ( ... )
+    Expression,
+    // < ... >
+    Condition,
+    // [ ... ]
+    Statement,
+    // { ... }
+    Body,
+    // a closing bracket without a matching opening bracket
+    Error,
+}
+impl NodeKind {
+    pub fn open(token: &Token) -> Option<Self> {
+        match token {
+            Token::ExprIn => Some(Self::Expression),
+            Token::CondIn => Some(Self::Condition),
+            Token::StatIn => Some(Self::Statement),
+            Token::BodyIn => Some(Self::Body),
+            _ => None
+        }
+    }
+    pub fn close(token: &Token) -> Option<Self> {
+        match token {
+            Token::ExprOut => Some(Self::Expression),
+            Token::CondOut => Some(Self::Condition),
+            Token::StatOut => Some(Self::Statement),
+            Token::BodyOut => Some(Self::Body),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Token(CstToken), Node(Node)
+}
+impl Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(token) => {
+                for trivia in &token.leading {
+                    write!(f, "{}", trivia.text)?;
+                }
+                write!(f, "{}", token.text)
+            }
+            Self::Node(node) => write!(f, "{node}")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<Element>
+}
+impl Node {
+    pub fn new(kind: NodeKind) -> Self {
+        Self { kind, children: vec![] }
+    }
+    // the opening and closing bracket tokens are the first and last children,
+    // a node left unclosed at the end of input has no closing token
+    pub fn closed(&self) -> bool {
+        match self.children.last() {
+            Some(Element::Token(token)) => NodeKind::close(&token.token.item) == Some(self.kind),
+            _ => false
+        }
+    }
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        let mut tokens = vec![];
+        for child in &self.children {
+            match child {
+                Element::Token(token) => tokens.push(token),
+                Element::Node(node) => tokens.append(&mut node.tokens())
+            }
+        }
+        tokens
+    }
+}
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for child in &self.children {
+            write!(f, "{child}")?;
+        }
+        Ok(())
+    }
+}
+
+// lossless syntax tree, printing it reproduces the source text byte for byte
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst {
+    pub root: Node,
+    // trivia after the last token
+    pub trailing: Vec<Trivia>
+}
+impl Cst {
+    pub fn new(root: Node, trailing: Vec<Trivia>) -> Self {
+        Self { root, trailing }
+    }
+}
+impl Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.root)?;
+        for trivia in &self.trailing {
+            write!(f, "{}", trivia.text)?;
+        }
+        Ok(())
+    }
+}
@@ -14,6 +14,14 @@ impl Error {
     pub fn msg<S: ToString>(msg: S) -> Self {
         Self { msg: msg.to_string(), path: FilePath::None, pos: None }
     }
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_ref()
+    }
+}
+impl From<Error> for Vec<Error> {
+    fn from(err: Error) -> Self {
+        vec![err]
+    }
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -4,85 +4,298 @@ use super::token::Token;
 pub struct Lexer {
     pub path: FilePath,
     text: String,
-    idx: usize, ln: usize, col: usize
+    idx: usize, ln: usize, col: usize,
+    // errors the lexer recovered from without failing the current token
+    errors: Vec<Error>
 }
 impl Lexer {
     pub fn new(path: FilePath, text: String) -> Self {
-        Self { path, text, idx: 0, ln: 0, col: 0 }
+        Self { path, text, idx: 0, ln: 0, col: 0, errors: vec![] }
     }
     pub fn get(&self) -> Option<char> {
-        self.text.get(self.idx..self.idx+1)?.chars().next()
+        self.text.get(self.idx..)?.chars().next()
     }
     pub fn pos(&self) -> Position {
-        Position::new(self.ln..self.ln+1, self.col..self.col+1)
+        let len = self.get().map(char::len_utf8).unwrap_or_default();
+        Position::new(self.idx..self.idx+len, self.ln..self.ln+1, self.col..self.col+1)
     }
     pub fn advance(&mut self) {
-        if self.get() == Some('\n') {
+        let Some(c) = self.get() else { return };
+        if c == '\n' {
             self.ln += 1;
             self.col = 0;
         } else {
             self.col += 1;
         }
-        self.idx += 1;
+        self.idx += c.len_utf8();
     }
 
-    pub fn next(&mut self) -> Result<Option<Located<Token>>, Error> {
+    pub fn peek(&self) -> Option<char> {
+        self.text.get(self.idx..)?.chars().nth(1)
+    }
+
+    // skips whitespace, `// line` and `/* block */` comments
+    pub fn skip(&mut self) -> Result<(), Error> {
         while let Some(c) = self.get() {
-            if !c.is_whitespace() { break; }
-            self.advance();
-        }
-        let mut pos = self.pos();
-        match self.get() {
-            Some(c) => match c {
-                '(' => {
-                    self.advance();
-                    Ok(Some(Located::new(Token::ExprIn, pos)))
+            match c {
+                c if c.is_whitespace() => self.advance(),
+                '/' if self.peek() == Some('/') => {
+                    while let Some(c) = self.get() {
+                        if c == '\n' { break; }
+                        self.advance();
+                    }
                 }
-                ')' => {
+                '/' if self.peek() == Some('*') => {
+                    let pos = self.pos();
                     self.advance();
-                    Ok(Some(Located::new(Token::ExprOut, pos)))
-                }
-                '"' => {
                     self.advance();
-                    let mut string = String::new();
-                    while let Some(c) = self.get() {
-                        if c == '"' { break; }
-                        string.push(c);
-                        self.advance();
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match (self.get(), self.peek()) {
+                            (Some('*'), Some('/')) => {
+                                depth -= 1;
+                                self.advance();
+                                self.advance();
+                            }
+                            (Some('/'), Some('*')) => {
+                                depth += 1;
+                                self.advance();
+                                self.advance();
+                            }
+                            (Some(_), _) => self.advance(),
+                            (None, _) => return Err(Error::new("unclosed block comment", self.path.clone(), Some(pos)))
+                        }
                     }
-                    if self.get() != Some('"') {
-                        return Err(Error::new(format!("unclosed string"), self.path.clone(), Some(self.pos())))
+                }
+                _ => break
+            }
+        }
+        Ok(())
+    }
+    pub fn symbol(&mut self, token: Token, pos: Position) -> Result<Option<Located<Token>>, Error> {
+        self.advance();
+        Ok(Some(Located::new(token, pos)))
+    }
+
+    // "..." with escapes, may span several lines
+    pub fn string(&mut self, mut pos: Position) -> Result<Option<Located<Token>>, Error> {
+        let start = self.idx;
+        self.advance();
+        let mut string = String::new();
+        let mut bad = false;
+        while let Some(c) = self.get() {
+            match c {
+                '"' => break,
+                // a backslash at the end of the input only leaves the string unclosed
+                '\\' if self.peek().is_none() => self.advance(),
+                '\\' => match self.escape() {
+                    Ok(c) => string.push(c),
+                    Err(err) => {
+                        self.errors.push(err);
+                        bad = true;
                     }
-                    pos.extend(&self.pos());
+                }
+                c => {
+                    string.push(c);
                     self.advance();
-                    Ok(Some(Located::new(Token::String(string), pos)))
                 }
-                c if c.is_digit(10) => {
-                    let mut number = String::from(c);
+            }
+        }
+        if self.get() != Some('"') {
+            return Err(Error::new("unclosed string", self.path.clone(), Some(pos)))
+        }
+        pos.extend(&self.pos());
+        self.advance();
+        if bad {
+            return Ok(Some(Located::new(Token::Error(self.text[start..self.idx].to_string()), pos)))
+        }
+        Ok(Some(Located::new(Token::String(string), pos)))
+    }
+    // \" \\ \n \t \r \0 \u{...}
+    pub fn escape(&mut self) -> Result<char, Error> {
+        let mut pos = self.pos();
+        self.advance();
+        let Some(c) = self.get() else {
+            return Err(Error::new("unclosed string", self.path.clone(), Some(pos)))
+        };
+        pos.extend(&self.pos());
+        self.advance();
+        match c {
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            'u' => {
+                if self.get() != Some('{') {
+                    return Err(Error::new("expected '{' after unicode escape", self.path.clone(), Some(pos)))
+                }
+                self.advance();
+                let mut code = String::new();
+                while let Some(c) = self.get() {
+                    if c == '}' || c == '"' { break; }
+                    code.push(c);
+                    pos.extend(&self.pos());
                     self.advance();
-                    while let Some(c) = self.get() {
-                        if !c.is_digit(10) { break; }
-                        number.push(c);
-                        pos.extend(&self.pos());
-                        self.advance();
-                    }
-                    if self.get() == Some('.') {
-                        number.push('.');
-                        pos.extend(&self.pos());
-                        self.advance();
-                        while let Some(c) = self.get() {
-                            if !c.is_digit(10) { break; }
-                            number.push(c);
-                            pos.extend(&self.pos());
-                            self.advance();
-                        }
-                    }
-                    match number.parse() {
-                        Ok(number) => Ok(Some(Located::new(Token::Number(number), pos))),
-                        Err(err) => Err(Error::new(format!("error while parsing number {number:?}: {err}"), self.path.clone(), Some(pos)))
+                }
+                if self.get() != Some('}') {
+                    return Err(Error::new("unclosed unicode escape", self.path.clone(), Some(pos)))
+                }
+                pos.extend(&self.pos());
+                self.advance();
+                if code.is_empty() || code.len() > 6 {
+                    return Err(Error::new(format!("invalid unicode escape {code:?}"), self.path.clone(), Some(pos)))
+                }
+                u32::from_str_radix(&code, 16).ok().and_then(char::from_u32)
+                    .ok_or_else(|| Error::new(format!("invalid unicode escape {code:?}"), self.path.clone(), Some(pos)))
+            }
+            c => Err(Error::new(format!("bad escape '\\{c}'"), self.path.clone(), Some(pos)))
+        }
+    }
+    pub fn is_raw_string(&self) -> bool {
+        let Some(rest) = self.text.get(self.idx+1..) else { return false };
+        rest.trim_start_matches('#').starts_with('"')
+    }
+    // r"..." or r#"..."# without escapes, the number of '#' has to match
+    pub fn raw_string(&mut self, mut pos: Position) -> Result<Option<Located<Token>>, Error> {
+        self.advance();
+        let mut hashes = 0;
+        while self.get() == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        self.advance();
+        let close = format!("\"{}", "#".repeat(hashes));
+        let mut string = String::new();
+        loop {
+            if self.text.get(self.idx..).is_some_and(|rest| rest.starts_with(&close)) { break; }
+            let Some(c) = self.get() else {
+                return Err(Error::new("unclosed raw string", self.path.clone(), Some(pos)))
+            };
+            string.push(c);
+            self.advance();
+        }
+        for _ in 0..=hashes {
+            pos.extend(&self.pos());
+            self.advance();
+        }
+        Ok(Some(Located::new(Token::String(string), pos)))
+    }
+
+    // collects digits of the radix into `number`, skipping '_' separators
+    pub fn digits(&mut self, number: &mut String, pos: &mut Position, radix: u32) {
+        while let Some(c) = self.get() {
+            if c == '_' {
+                pos.extend(&self.pos());
+                self.advance();
+                continue;
+            }
+            if !c.is_digit(radix) { break; }
+            number.push(c);
+            pos.extend(&self.pos());
+            self.advance();
+        }
+    }
+    // 0x1F, 0b101, 0o17, 1_000, 1.5, .5, 1e-3
+    pub fn number(&mut self, mut pos: Position) -> Result<Option<Located<Token>>, Error> {
+        if self.get() == Some('0') {
+            let radix = match self.peek() {
+                Some('x' | 'X') => Some((16, "hexadecimal")),
+                Some('b' | 'B') => Some((2, "binary")),
+                Some('o' | 'O') => Some((8, "octal")),
+                _ => None
+            };
+            if let Some((radix, name)) = radix {
+                let prefix = format!("0{}", self.peek().unwrap_or_default());
+                self.advance();
+                pos.extend(&self.pos());
+                self.advance();
+                let mut number = String::new();
+                self.digits(&mut number, &mut pos, radix);
+                if number.is_empty() {
+                    return Err(Error::new(format!("expected {name} digits after '{prefix}'"), self.path.clone(), Some(pos)))
+                }
+                self.number_end(&mut pos)?;
+                let literal = format!("{prefix}{number}");
+                return match u128::from_str_radix(&number, radix) {
+                    // numbers are f64, bigger integers would silently lose their lowest bits
+                    Ok(number) if number as f64 as u128 != number => {
+                        Err(Error::new(format!("{literal} can't be represented exactly, the closest number is {}", number as f64), self.path.clone(), Some(pos)))
                     }
+                    Ok(number) => Ok(Some(Located::new(Token::Number(number as f64), pos))),
+                    Err(err) => Err(Error::new(format!("error while parsing number {literal:?}: {err}"), self.path.clone(), Some(pos)))
                 }
-                c if c.is_alphabetic() => {
+            }
+        }
+        let mut number = String::new();
+        self.digits(&mut number, &mut pos, 10);
+        if self.get() == Some('.') {
+            number.push('.');
+            pos.extend(&self.pos());
+            self.advance();
+            self.digits(&mut number, &mut pos, 10);
+        }
+        if let Some(e @ ('e' | 'E')) = self.get() {
+            number.push(e);
+            pos.extend(&self.pos());
+            self.advance();
+            if let Some(sign @ ('+' | '-')) = self.get() {
+                number.push(sign);
+                pos.extend(&self.pos());
+                self.advance();
+            }
+            let len = number.len();
+            self.digits(&mut number, &mut pos, 10);
+            if number.len() == len {
+                return Err(Error::new(format!("expected exponent digits after {number:?}"), self.path.clone(), Some(pos)))
+            }
+        }
+        self.number_end(&mut pos)?;
+        match number.parse() {
+            Ok(number) => Ok(Some(Located::new(Token::Number(number), pos))),
+            Err(err) => Err(Error::new(format!("error while parsing number {number:?}: {err}"), self.path.clone(), Some(pos)))
+        }
+    }
+    // a number directly followed by letters or digits like `12ab` or `0b12` is malformed
+    pub fn number_end(&mut self, pos: &mut Position) -> Result<(), Error> {
+        let mut bad = None;
+        while let Some(c) = self.get() {
+            if !c.is_alphanumeric() && c != '_' { break; }
+            bad = bad.or(Some(c));
+            pos.extend(&self.pos());
+            self.advance();
+        }
+        if let Some(c) = bad {
+            return Err(Error::new(format!("malformed number, unexpected {c:?}"), self.path.clone(), Some(pos.clone())))
+        }
+        Ok(())
+    }
+
+    pub fn next(&mut self) -> Result<Option<Located<Token>>, Error> {
+        self.skip()?;
+        let mut pos = self.pos();
+        match self.get() {
+            Some(c) => match c {
+                ';' => self.symbol(Token::End, pos),
+                ',' => self.symbol(Token::Sep, pos),
+                ':' => self.symbol(Token::Rep, pos),
+                '=' => self.symbol(Token::Equal, pos),
+                '#' => self.symbol(Token::Def, pos),
+                '|' => self.symbol(Token::Trigger, pos),
+                '(' => self.symbol(Token::ExprIn, pos),
+                ')' => self.symbol(Token::ExprOut, pos),
+                '<' => self.symbol(Token::CondIn, pos),
+                '>' => self.symbol(Token::CondOut, pos),
+                '[' => self.symbol(Token::StatIn, pos),
+                ']' => self.symbol(Token::StatOut, pos),
+                '{' => self.symbol(Token::BodyIn, pos),
+                '}' => self.symbol(Token::BodyOut, pos),
+                '"' => self.string(pos),
+                'r' if self.is_raw_string() => self.raw_string(pos),
+                c if c.is_ascii_digit() => self.number(pos),
+                '.' if self.peek().is_some_and(|c| c.is_ascii_digit()) => self.number(pos),
+                c if c.is_alphabetic() || c == '_' => {
                     let mut word = String::from(c);
                     self.advance();
                     while let Some(c) = self.get() {
@@ -93,17 +306,54 @@ impl Lexer {
                     }
                     Ok(Some(Located::new(Token::from_word(word), pos)))
                 }
-                _ => todo!("lex token, char {c:?}")
+                c => Err(Error::new(format!("bad character {c:?}"), self.path.clone(), Some(pos)))
             }
             None => Ok(None)
         }
     }
-    pub fn lex(&mut self) -> Result<Vec<Located<Token>>, Error> {
+    // skips a bad region that `next` failed on without moving past it
+    pub fn recover(&mut self) {
+        self.advance();
+        while let Some(c) = self.get() {
+            if c.is_whitespace() || c.is_alphanumeric() || "_;,:=#|()<>[]{}\"".contains(c) { break; }
+            self.advance();
+        }
+    }
+    // lexes the whole text, turning every bad region into a `Token::Error`
+    // and collecting all lexical errors instead of stopping at the first one
+    pub fn lex_recovering(&mut self) -> (Vec<Located<Token>>, Vec<Error>) {
         let mut tokens = vec![];
-        while let Some(token) = self.next()? {
-            tokens.push(token);
+        let mut errors = vec![];
+        loop {
+            if let Err(err) = self.skip() {
+                errors.push(err);
+            }
+            let start = self.idx;
+            let mut pos = self.pos();
+            let result = self.next();
+            errors.append(&mut self.errors);
+            match result {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => break,
+                Err(err) => {
+                    errors.push(err);
+                    if self.idx == start {
+                        self.recover();
+                    }
+                    pos.extend(&Position::new(self.idx..self.idx, self.ln..self.ln+1, self.col..self.col));
+                    tokens.push(Located::new(Token::Error(self.text[start..self.idx].to_string()), pos));
+                }
+            }
+        }
+        // errors inside of a token are found before the error of the whole token
+        errors.sort_by_key(|err| err.pos().map(|pos| pos.idx.start));
+        (tokens, errors)
+    }
+    pub fn lex(&mut self) -> Result<Vec<Located<Token>>, Error> {
+        let (tokens, mut errors) = self.lex_recovering();
+        if !errors.is_empty() {
+            return Err(errors.remove(0))
         }
         Ok(tokens)
     }
 }
-
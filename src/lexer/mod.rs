@@ -1,11 +1,19 @@
 use crate::{location::{path::FilePath, position::Located}, error::Error};
 
+#[allow(clippy::module_inception)]
 pub mod lexer;
 pub mod token;
 
 use lexer::Lexer;
 use token::Token;
 
-pub fn lex(path: &FilePath, text: String) -> Result<Vec<Located<Token>>, Error> {
-    Lexer::new(path.clone(), text).lex()
-}
\ No newline at end of file
+pub fn lex(path: &FilePath, text: String) -> Result<Vec<Located<Token>>, Vec<Error>> {
+    let (tokens, errors) = Lexer::new(path.clone(), text).lex_recovering();
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests;
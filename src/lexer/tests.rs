@@ -0,0 +1,110 @@
+use crate::{location::path::FilePath, testing::located};
+use super::{lex, lexer::Lexer, token::Token};
+
+fn tokens(text: &str) -> Vec<Token> {
+    lex(&FilePath::Input(text.to_string()), text.to_string()).unwrap().into_iter().map(|token| token.item).collect()
+}
+// every error of lexing the text
+fn errors(text: &str) -> Vec<String> {
+    let errors = lex(&FilePath::Input(text.to_string()), text.to_string()).unwrap_err();
+    errors.iter().map(located).collect()
+}
+fn word(word: &str) -> Token {
+    Token::Word(word.to_string())
+}
+
+#[test]
+fn punctuation() {
+    use Token::*;
+    assert_eq!(tokens(";,:=#|()<>[]{}"), vec![
+        End, Sep, Rep, Equal, Def, Trigger, ExprIn, ExprOut, CondIn, CondOut, StatIn, StatOut, BodyIn, BodyOut
+    ]);
+}
+
+#[test]
+fn comments_are_skipped() {
+    assert_eq!(tokens("a // b ( [\nc"), vec![word("a"), word("c")]);
+    assert_eq!(tokens("a /* b /* nested */ c */ d"), vec![word("a"), word("d")]);
+    assert_eq!(tokens("a/**/b//"), vec![word("a"), word("b")]);
+}
+
+#[test]
+fn unclosed_block_comment() {
+    assert_eq!(errors("a /* b /* c */"), vec!["1:3: unclosed block comment"]);
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(tokens(r#""a\"b\\c\nd\te\rf\0g\u{1F600}""#), vec![Token::String("a\"b\\c\nd\te\rf\0g\u{1F600}".to_string())]);
+}
+
+#[test]
+fn raw_and_multi_line_strings() {
+    assert_eq!(tokens(r##"r"a\n" r#"say "hi""#"##), vec![Token::String("a\\n".to_string()), Token::String("say \"hi\"".to_string())]);
+    assert_eq!(tokens("\"a\nb\""), vec![Token::String("a\nb".to_string())]);
+    // `r` alone is still a word
+    assert_eq!(tokens("r rx"), vec![word("r"), word("rx")]);
+}
+
+#[test]
+fn bad_strings() {
+    assert_eq!(errors(r#""a\qb""#), vec!["1:3: bad escape '\\q'"]);
+    assert_eq!(errors(r#""\u{110000}""#), vec!["1:2: invalid unicode escape \"110000\""]);
+    assert_eq!(errors("\"abc"), vec!["1:1: unclosed string"]);
+    assert_eq!(errors("\"abc\\"), vec!["1:1: unclosed string"]);
+    assert_eq!(errors("r#\"abc\""), vec!["1:1: unclosed raw string"]);
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(tokens("0x1F 0b101 0o17 1_000 1.5 .5 1e-3 2E+2 7."), [31., 5., 15., 1000., 1.5, 0.5, 0.001, 200., 7.].map(Token::Number).to_vec());
+    // integers bigger than 2^53 are fine as long as they are exact
+    assert_eq!(tokens("0x20000000000000 0x1_0000_0000_0000_0000"), [9007199254740992., 18446744073709551616.].map(Token::Number).to_vec());
+}
+
+#[test]
+fn bad_numbers() {
+    assert_eq!(errors("0x"), vec!["1:1: expected hexadecimal digits after '0x'"]);
+    assert_eq!(errors("1e"), vec!["1:1: expected exponent digits after \"1e\""]);
+    assert_eq!(errors("0b12"), vec!["1:1: malformed number, unexpected '2'"]);
+    assert_eq!(errors("12ab"), vec!["1:1: malformed number, unexpected 'a'"]);
+    assert_eq!(errors("0x20000000000001"), vec!["1:1: 0x20000000000001 can't be represented exactly, the closest number is 9007199254740992"]);
+}
+
+#[test]
+fn positions_count_bytes_and_chars() {
+    let text = "été \"ü\"\n  x";
+    let tokens = lex(&FilePath::Input(text.to_string()), text.to_string()).unwrap();
+    let positions = tokens.iter().map(|token| (token.pos.idx.clone(), token.pos.ln.start, token.pos.col.clone())).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(0..5, 0, 0..3), (6..10, 0, 4..7), (13..14, 1, 2..3)]);
+    assert_eq!(&text[tokens[1].pos.idx.clone()], "\"ü\"");
+    assert_eq!(tokens[0].item, word("été"));
+}
+
+#[test]
+fn error_columns_count_chars() {
+    assert_eq!(errors("ü @"), vec!["1:3: bad character '@'"]);
+    assert_eq!(errors("a\n  ü \\q"), vec!["2:5: bad character '\\\\'"]);
+}
+
+#[test]
+fn every_error_is_reported() {
+    assert_eq!(errors("a @ \"\\q\" 0x [b] 1e"), vec![
+        "1:3: bad character '@'",
+        "1:6: bad escape '\\q'",
+        "1:10: expected hexadecimal digits after '0x'",
+        "1:17: expected exponent digits after \"1e\"",
+    ]);
+    // an error inside of a token comes after the error of the whole token
+    assert_eq!(errors("\"a\\q"), vec!["1:1: unclosed string", "1:3: bad escape '\\q'"]);
+}
+
+#[test]
+fn bad_regions_become_error_tokens() {
+    let (tokens, errors) = Lexer::new(FilePath::None, "[a @$ \"\\q\" b]".to_string()).lex_recovering();
+    let tokens = tokens.into_iter().map(|token| token.item).collect::<Vec<Token>>();
+    assert_eq!(tokens, vec![
+        Token::StatIn, word("a"), Token::Error("@$".to_string()), Token::Error("\"\\q\"".to_string()), word("b"), Token::StatOut
+    ]);
+    assert_eq!(errors.len(), 2);
+}
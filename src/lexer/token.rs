@@ -3,11 +3,13 @@ use std::fmt::Display;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Word(String), Number(f64), Bool(bool), String(String),
-    End, Sep, Rep, Equal,
+    End, Sep, Rep, Equal, Def, Trigger,
     ExprIn, ExprOut,
     CondIn, CondOut,
     StatIn, StatOut,
     BodyIn, BodyOut,
+    // source text of a region the lexer could not make sense of
+    Error(String),
 }
 impl Token {
     pub fn from_word(word: String) -> Self {
@@ -19,11 +21,12 @@ impl Token {
     }
     pub fn name(&self) -> String {
         match self {
-            Self::Word(_) => format!("identifier"),
-            Self::Number(_) => format!("number"),
-            Self::Bool(_) => format!("boolean"),
-            Self::String(_) => format!("string"),
-            _ => format!("'{self}'")
+            Self::Word(_) => "identifier".to_string(),
+            Self::Number(_) => "number".to_string(),
+            Self::Bool(_) => "boolean".to_string(),
+            Self::String(_) => "string".to_string(),
+            Self::Error(_) => "invalid token".to_string(),
+            _ => format!("`{self}`")
         }
     }
 }
@@ -38,6 +41,8 @@ impl Display for Token {
             Self::Sep => write!(f, ","),
             Self::Rep => write!(f, ":"),
             Self::Equal => write!(f, "="),
+            Self::Def => write!(f, "#"),
+            Self::Trigger => write!(f, "|"),
             Self::ExprIn => write!(f, "("),
             Self::ExprOut => write!(f, ")"),
             Self::CondIn => write!(f, "<"),
@@ -46,6 +51,7 @@ impl Display for Token {
             Self::StatOut => write!(f, "]"),
             Self::BodyIn => write!(f, "{{"),
             Self::BodyOut => write!(f, "}}"),
+            Self::Error(text) => write!(f, "{text}"),
         }
     }
 }
\ No newline at end of file
@@ -2,29 +2,46 @@ use std::{ops::Range, fmt::{Debug, Display}};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Position {
+    // byte offsets into the source text
+    pub idx: Range<usize>,
     pub ln: Range<usize>,
+    // counted in chars, not bytes
     pub col: Range<usize>,
 }
 impl Position {
-    pub fn new(ln: Range<usize>, col: Range<usize>) -> Self {
-        Self { ln, col }
+    pub fn new(idx: Range<usize>, ln: Range<usize>, col: Range<usize>) -> Self {
+        Self { idx, ln, col }
     }
     pub fn extend(&mut self, pos: &Self) {
+        self.idx.end = pos.idx.end;
         self.ln.end = pos.ln.end;
         self.col.end = pos.col.end;
     }
+    // the empty position right after the end of this one
+    pub fn end(&self) -> Self {
+        Self::new(self.idx.end..self.idx.end, self.ln.end.saturating_sub(1)..self.ln.end, self.col.end..self.col.end+1)
+    }
+    pub fn idx(self) -> Range<usize> {
+        self.idx
+    }
     pub fn ln(self) -> Range<usize> {
         self.ln
     }
     pub fn col(self) -> Range<usize> {
         self.col
     }
+    pub fn idx_ref(&self) -> &Range<usize> {
+        &self.idx
+    }
     pub fn ln_ref(&self) -> &Range<usize> {
         &self.ln
     }
     pub fn col_ref(&self) -> &Range<usize> {
         &self.col
     }
+    pub fn idx_mut(&mut self) -> &mut Range<usize> {
+        &mut self.idx
+    }
     pub fn ln_mut(&mut self) -> &mut Range<usize> {
         &mut self.ln
     }
@@ -79,10 +96,10 @@ macro_rules! locate {
     ($item:expr, $pos:expr) => {
         self::Located::new($item, $pos)
     };
-    ($item:expr, $ln:expr, $col:expr) => {
-        self::Located::new($item, self::Position::new($ln, $col))
+    ($item:expr, $idx:expr, $ln:expr, $col:expr) => {
+        self::Located::new($item, self::Position::new($idx, $ln, $col))
     };
-    ($item:expr, $ln_start:expr, $ln_end:expr, $col_start:expr, $col_end:expr) => {
-        self::Located::new($item, self::Position::new($ln_start..$ln_end, $col_start..$col_end))
+    ($item:expr, $idx_start:expr, $idx_end:expr, $ln_start:expr, $ln_end:expr, $col_start:expr, $col_end:expr) => {
+        self::Located::new($item, self::Position::new($idx_start..$idx_end, $ln_start..$ln_end, $col_start..$col_end))
     };
 }
\ No newline at end of file
@@ -4,23 +4,38 @@ mod location;
 mod error;
 mod lexer;
 mod parser;
+mod cst;
+mod analysis;
+mod runtime;
+#[cfg(test)]
+mod testing;
 
-use std::{env, fs, process::exit, io::{self, Write}};
+use std::{env, fs, process::exit, io::{self, Write}, thread};
 use arguments::Arguments;
 use error::Error;
 use location::path::FilePath;
 
-pub fn run(path: FilePath, text: String, arguments: &Arguments) -> Result<(), Error> {
+pub fn run(path: FilePath, text: String, arguments: &Arguments) -> Result<(), Vec<Error>> {
     let tokens = lexer::lex(&path, text)?;
     if arguments.get_flag("tokens") { println!("{}", join!(tokens, " ")) }
-    todo!("run()")
+    let program = parser::parse(&path, tokens)?;
+    if arguments.get_flag("ast") { println!("{program:#?}") }
+    let resolution = analysis::resolve(&path, &program)?;
+    let types = analysis::check(&path, &program, &resolution)?;
+    let runtime = runtime::run(&path, &program, &resolution, &types, arguments)?;
+    if arguments.get_flag("data") {
+        for (name, value) in runtime.interpreter.variables() {
+            println!("{name} = {value}");
+        }
+    }
+    Ok(())
 }
 
-fn entry() -> Result<(), Error> {
-    let mut arguments = Arguments::new().args(env::args()).map_err(|msg| Error::msg(msg))?;
+fn entry() -> Result<(), Vec<Error>> {
+    let mut arguments = Arguments::new().args(env::args()).map_err(Error::msg)?;
     if let Some(path) = arguments.next_input() {
         let Ok(text) = fs::read_to_string(&path) else {
-            return cant_open_file_error!(path);
+            return cant_open_file_error!(path).map_err(Vec::from);
         };
         run(FilePath::Path(path), text, &arguments)?;
     } else {
@@ -36,8 +51,13 @@ fn entry() -> Result<(), Error> {
 }
 
 fn main() {
-    if let Some(err) = entry().err() {
-        eprintln!("ERROR: {err}");
+    // scripts recurse deeper than the stack of the main thread allows
+    let entry = thread::Builder::new().stack_size(runtime::interpreter::STACK_SIZE).spawn(entry).expect("couldn't start the main thread");
+    let Ok(result) = entry.join() else { exit(101) };
+    if let Some(errors) = result.err() {
+        for err in errors {
+            eprintln!("ERROR: {err}");
+        }
         exit(1);
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt::Display;
 use crate::error::Error;
 use crate::location::position::{Located, Position};
 use crate::lexer::token::*;
@@ -7,6 +8,49 @@ use super::parser::*;
 pub enum ValueType {
     Expression, Condition
 }
+impl ValueType {
+    // the kind of value a token starts, identifiers can be either
+    pub fn of(token: &Token) -> Option<Self> {
+        match token {
+            Token::ExprIn | Token::Number(_) | Token::String(_) => Some(Self::Expression),
+            Token::CondIn | Token::Bool(_) => Some(Self::Condition),
+            _ => None
+        }
+    }
+}
+impl Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expression => write!(f, "expression"),
+            Self::Condition => write!(f, "condition"),
+        }
+    }
+}
+
+// the type of a value, expressions can be of any type while conditions are always bool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Number, String, Bool
+}
+impl Type {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "number" => Some(Self::Number),
+            "string" => Some(Self::String),
+            "bool" => Some(Self::Bool),
+            _ => None
+        }
+    }
+}
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::String => write!(f, "string"),
+            Self::Bool => write!(f, "bool"),
+        }
+    }
+}
 
 // < ... >
 #[derive(Debug, Clone, PartialEq)]
@@ -21,6 +65,99 @@ pub enum Condition {
     GT(Located<Expression>, Located<Expression>),
     LE(Located<Expression>, Located<Expression>),
     GE(Located<Expression>, Located<Expression>),
+    ID(String),
+    Call(Located<String>, Vec<Argument>),
+}
+impl Condition {
+    // the type and number of operands an operator takes
+    pub fn operands(op: &str) -> Option<(ValueType, usize)> {
+        match op {
+            "and" | "or" => Some((ValueType::Condition, 2)),
+            "not" => Some((ValueType::Condition, 1)),
+            "eq" | "ne" | "lt" | "gt" | "le" | "ge" => Some((ValueType::Expression, 2)),
+            _ => None
+        }
+    }
+    pub fn logic(op: &str, args: Vec<Located<Self>>) -> Option<Self> {
+        let mut args = args.into_iter().map(Box::new);
+        let mut arg = || args.next();
+        Some(match op {
+            "and" => Self::And(arg()?, arg()?),
+            "or" => Self::Or(arg()?, arg()?),
+            "not" => Self::Not(arg()?),
+            _ => return None
+        })
+    }
+    pub fn comparison(op: &str, args: Vec<Located<Expression>>) -> Option<Self> {
+        let mut args = args.into_iter();
+        let mut arg = || args.next();
+        Some(match op {
+            "eq" => Self::EQ(arg()?, arg()?),
+            "ne" => Self::NE(arg()?, arg()?),
+            "lt" => Self::LT(arg()?, arg()?),
+            "gt" => Self::GT(arg()?, arg()?),
+            "le" => Self::LE(arg()?, arg()?),
+            "ge" => Self::GE(arg()?, arg()?),
+            _ => return None
+        })
+    }
+}
+// ID
+// BOOL
+// <OP ARGS>
+// <ID ARGS>
+// <COND>
+impl Parsable for Condition {
+    fn name() -> &'static str {
+        "condition"
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: token, mut pos } = parser.token_check()?;
+        match token {
+            Token::Word(id) => Ok(Located::new(Self::ID(id), pos)),
+            Token::Bool(true) => Ok(Located::new(Self::True, pos)),
+            Token::Bool(false) => Ok(Located::new(Self::False, pos)),
+            Token::CondIn => {
+                let Some(Located { item: Token::Word(op), pos: op_pos }) = parser.token_ref().cloned() else {
+                    let Located { item: cond, pos: _ } = Self::parse(parser)?;
+                    let Located { item: _, pos: end_pos } = parser.token_expect(Token::CondOut)?;
+                    pos.extend(&end_pos);
+                    return Ok(Located::new(cond, pos))
+                };
+                let Some((typ, arity)) = Self::operands(&op) else {
+                    parser.token();
+                    let args = Argument::parse_args(parser, Token::CondOut)?;
+                    let Located { item: _, pos: end_pos } = parser.token_expect(Token::CondOut)?;
+                    pos.extend(&end_pos);
+                    return Ok(Located::new(Self::Call(Located::new(op, op_pos), args), pos))
+                };
+                parser.token();
+                let (mut conds, mut exprs) = (vec![], vec![]);
+                while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
+                    if token == &Token::CondOut { break; }
+                    match typ {
+                        ValueType::Condition => conds.push(Self::parse(parser)?),
+                        ValueType::Expression => exprs.push(Expression::parse(parser)?),
+                    }
+                }
+                let Located { item: _, pos: end_pos } = parser.token_expect(Token::CondOut)?;
+                pos.extend(&end_pos);
+                let len = conds.len() + exprs.len();
+                if len != arity {
+                    return Err(Error::new(format!("{op:?} expects {arity} arguments, got {len}"), parser.path.clone(), Some(pos)))
+                }
+                let cond = match typ {
+                    ValueType::Condition => Self::logic(&op, conds),
+                    ValueType::Expression => Self::comparison(&op, exprs),
+                };
+                Ok(Located::new(cond.expect("checked arity"), pos))
+            }
+            token => match ValueType::of(&token) {
+                Some(typ) => Err(Error::new(format!("expected {}, got {typ}", ValueType::Condition), parser.path.clone(), Some(pos))),
+                None => Err(Error::new(format!("expected {}, got {}", ValueType::Condition, token.name()), parser.path.clone(), Some(pos)))
+            }
+        }
+    }
 }
 
 // ( ... )
@@ -43,6 +180,87 @@ pub enum Expression {
     Index(Box<Located<Self>>, Box<Located<Self>>),
     SubString(Box<Located<Self>>, Box<Located<Self>>, Box<Located<Self>>),
     Contains(Box<Located<Self>>, Box<Located<Self>>),
+
+    Call(Located<String>, Vec<Argument>),
+}
+impl Expression {
+    pub fn arity(op: &str) -> Option<usize> {
+        match op {
+            "add" | "sub" | "mul" | "div" | "pow" | "mod" | "max" | "min" => Some(2),
+            "join" | "index" | "contains" => Some(2),
+            "substring" => Some(3),
+            _ => None
+        }
+    }
+    pub fn operation(op: &str, args: Vec<Located<Self>>) -> Option<Self> {
+        let mut args = args.into_iter().map(Box::new);
+        let mut arg = || args.next();
+        Some(match op {
+            "add" => Self::Add(arg()?, arg()?),
+            "sub" => Self::Sub(arg()?, arg()?),
+            "mul" => Self::Mul(arg()?, arg()?),
+            "div" => Self::Div(arg()?, arg()?),
+            "pow" => Self::Pow(arg()?, arg()?),
+            "mod" => Self::Mod(arg()?, arg()?),
+            "max" => Self::Max(arg()?, arg()?),
+            "min" => Self::Min(arg()?, arg()?),
+            "join" => Self::Join(arg()?, arg()?),
+            "index" => Self::Index(arg()?, arg()?),
+            "substring" => Self::SubString(arg()?, arg()?, arg()?),
+            "contains" => Self::Contains(arg()?, arg()?),
+            _ => return None
+        })
+    }
+}
+// ID
+// NUMBER
+// STRING
+// (OP ARGS)
+// (ID ARGS)
+// (EXPR)
+impl Parsable for Expression {
+    fn name() -> &'static str {
+        "expression"
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: token, mut pos } = parser.token_check()?;
+        match token {
+            Token::Word(id) => Ok(Located::new(Self::ID(id), pos)),
+            Token::Number(number) => Ok(Located::new(Self::Number(number), pos)),
+            Token::String(string) => Ok(Located::new(Self::String(string), pos)),
+            Token::ExprIn => {
+                let Some(Located { item: Token::Word(op), pos: op_pos }) = parser.token_ref().cloned() else {
+                    let Located { item: expr, pos: _ } = Self::parse(parser)?;
+                    let Located { item: _, pos: end_pos } = parser.token_expect(Token::ExprOut)?;
+                    pos.extend(&end_pos);
+                    return Ok(Located::new(expr, pos))
+                };
+                let Some(arity) = Self::arity(&op) else {
+                    parser.token();
+                    let args = Argument::parse_args(parser, Token::ExprOut)?;
+                    let Located { item: _, pos: end_pos } = parser.token_expect(Token::ExprOut)?;
+                    pos.extend(&end_pos);
+                    return Ok(Located::new(Self::Call(Located::new(op, op_pos), args), pos))
+                };
+                parser.token();
+                let mut args = vec![];
+                while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
+                    if token == &Token::ExprOut { break; }
+                    args.push(Self::parse(parser)?);
+                }
+                let Located { item: _, pos: end_pos } = parser.token_expect(Token::ExprOut)?;
+                pos.extend(&end_pos);
+                if args.len() != arity {
+                    return Err(Error::new(format!("{op:?} expects {arity} arguments, got {}", args.len()), parser.path.clone(), Some(pos)))
+                }
+                Ok(Located::new(Self::operation(&op, args).expect("checked arity"), pos))
+            }
+            token => match ValueType::of(&token) {
+                Some(typ) => Err(Error::new(format!("expected {}, got {typ}", ValueType::Expression), parser.path.clone(), Some(pos))),
+                None => Err(Error::new(format!("expected {}, got {}", ValueType::Expression, token.name()), parser.path.clone(), Some(pos)))
+            }
+        }
+    }
 }
 
 // [ ... ]
@@ -57,51 +275,322 @@ pub enum Statement {
     Loop(Located<Block>),
     Break, Next,
     Return(Located<Expression>),
-    ReturnCondition(Located<Condition>)
+    ReturnCondition(Located<Condition>),
+    Call(Located<String>, Vec<Argument>),
+    // pauses the script for a number of ticks
+    Wait(Located<Expression>),
+    // starts every event triggered by the message
+    Broadcast(Located<String>, Vec<Argument>),
+    // starts every event triggered by the message and waits for all of them to finish
+    BroadcastWait(Located<String>, Vec<Argument>),
+    // sends the message like a broadcast and waits until a handler returns a value into the variable,
+    // at most for the number of ticks of the timeout
+    Request(Located<String>, Located<String>, Vec<Argument>, Option<Located<Expression>>),
+    // starts a clone of the actor, of the running actor or clone without a name
+    Clone(Option<Located<String>>),
+    // stops every script of the running clone and removes it
+    DeleteClone,
+    // a statement that failed to parse
+    Error,
+}
+impl Statement {
+    // loop bodies are parsed with `parser.loops` raised so `[break]` and `[next]` can be checked
+    pub fn parse_loop_body(parser: &mut Parser) -> Result<Located<Block>, Error> {
+        parser.loops += 1;
+        let body = Block::parse(parser);
+        parser.loops -= 1;
+        body
+    }
+}
+// [set ID EXPR]
+// [set ID COND]
+// [if COND BLOCK]
+// [if COND BLOCK else BLOCK]
+// [repeat EXPR BLOCK]
+// [while COND BLOCK]
+// [loop BLOCK]
+// [break]
+// [next]
+// [return EXPR]
+// [return COND]
+// [wait EXPR]
+// [broadcast ID ARGS]
+// [broadcast_wait ID ARGS]
+// [request ID ID ARGS]
+// [request ID ID ARGS timeout EXPR]
+// [clone]
+// [clone ID]
+// [delete_clone]
+// [ID ARGS]
+impl Parsable for Statement {
+    fn name() -> &'static str {
+        "statement"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::StatIn)
+    }
+    fn error() -> Option<Self> {
+        Some(Self::Error)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: _, mut pos } = parser.token_expect(Token::StatIn)?;
+        let Located { item: word, pos: word_pos } = parser.word()?;
+        let stat = match word.as_str() {
+            "set" => {
+                let id = parser.word()?;
+                match parser.token_ref().and_then(|token| ValueType::of(&token.item)) {
+                    Some(ValueType::Condition) => Self::SetCondition(id, Condition::parse(parser)?),
+                    _ => Self::Set(id, Expression::parse(parser)?)
+                }
+            }
+            "if" => {
+                let cond = Condition::parse(parser)?;
+                let case = Block::parse(parser)?;
+                match parser.token_ref() {
+                    Some(Located { item: Token::Word(word), pos: _ }) if word == "else" => {
+                        parser.token();
+                        if !parser.check(&Token::BodyIn) {
+                            return Err(parser.unexpected(&[Token::BodyIn], Some("after `else`")))
+                        }
+                        Self::IfElse(cond, case, Block::parse(parser)?)
+                    }
+                    _ => Self::If(cond, case)
+                }
+            }
+            "repeat" => Self::Repeat(Expression::parse(parser)?, Self::parse_loop_body(parser)?),
+            "while" => Self::While(Condition::parse(parser)?, Self::parse_loop_body(parser)?),
+            "loop" => Self::Loop(Self::parse_loop_body(parser)?),
+            "break" | "next" => {
+                if parser.loops == 0 {
+                    return Err(Error::new(format!("{word:?} outside of a loop"), parser.path.clone(), Some(word_pos)))
+                }
+                if word == "break" { Self::Break } else { Self::Next }
+            }
+            "return" => {
+                let return_type = match parser.function {
+                    Some(return_type) => return_type,
+                    // events can reply with any value
+                    None if parser.event => parser.token_ref().and_then(|token| ValueType::of(&token.item)).unwrap_or(ValueType::Expression),
+                    None => return Err(Error::new("\"return\" outside of a function or event", parser.path.clone(), Some(word_pos)))
+                };
+                if let Some(Located { item: token, pos: value_pos }) = parser.token_ref() {
+                    if let Some(typ) = ValueType::of(token).filter(|typ| typ != &return_type) {
+                        return Err(Error::new(format!("expected {return_type} to return, got {typ}"), parser.path.clone(), Some(value_pos.clone())))
+                    }
+                }
+                match return_type {
+                    ValueType::Expression => Self::Return(Expression::parse(parser)?),
+                    ValueType::Condition => Self::ReturnCondition(Condition::parse(parser)?),
+                }
+            }
+            "wait" => {
+                if parser.function.is_some() {
+                    return Err(Error::new("\"wait\" inside of a function", parser.path.clone(), Some(word_pos)))
+                }
+                Self::Wait(Expression::parse(parser)?)
+            }
+            "broadcast" | "broadcast_wait" => {
+                if parser.function.is_some() {
+                    return Err(Error::new(format!("{word:?} inside of a function"), parser.path.clone(), Some(word_pos)))
+                }
+                let id = parser.word()?;
+                let args = Argument::parse_args(parser, Token::StatOut)?;
+                if word == "broadcast" { Self::Broadcast(id, args) } else { Self::BroadcastWait(id, args) }
+            }
+            "request" => {
+                if parser.function.is_some() {
+                    return Err(Error::new("\"request\" inside of a function", parser.path.clone(), Some(word_pos)))
+                }
+                let var = parser.word()?;
+                let id = parser.word()?;
+                let mut args = vec![];
+                let mut timeout = None;
+                while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
+                    if token == &Token::StatOut { break; }
+                    if let Token::Word(word) = token {
+                        if word == "timeout" {
+                            parser.token();
+                            timeout = Some(Expression::parse(parser)?);
+                            break;
+                        }
+                    }
+                    args.push(Argument::parse(parser)?);
+                }
+                Self::Request(var, id, args, timeout)
+            }
+            "clone" | "delete_clone" => {
+                if parser.function.is_some() {
+                    return Err(Error::new(format!("{word:?} inside of a function"), parser.path.clone(), Some(word_pos)))
+                }
+                if word == "delete_clone" {
+                    Self::DeleteClone
+                } else if let Some(Located { item: Token::Word(_), pos: _ }) = parser.token_ref() {
+                    Self::Clone(Some(parser.word()?))
+                } else {
+                    Self::Clone(None)
+                }
+            }
+            _ => Self::Call(Located::new(word.clone(), word_pos), Argument::parse_args(parser, Token::StatOut)?)
+        };
+        let Located { item: _, pos: end_pos } = parser.token_expect(Token::StatOut)?;
+        pos.extend(&end_pos);
+        Ok(Located::new(stat, pos))
+    }
 }
 
 // { ... }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
-    statements: Vec<Statement>
+    pub statements: Vec<Located<Statement>>
+}
+impl Block {
+    pub fn new(statements: Vec<Located<Statement>>) -> Self {
+        Self { statements }
+    }
+}
+// { STAT; STAT; ... }
+impl Parsable for Block {
+    fn name() -> &'static str {
+        "block"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::BodyIn)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: statements, pos } = parser.body()?;
+        Ok(Located::new(Self::new(statements), pos))
+    }
 }
 
+// |start|
+// |clone|
+// |ID PARAMS|
+// |COND|
 #[derive(Debug, Clone, PartialEq)]
-pub enum Trigger {}
+pub enum Trigger {
+    Start,
+    // a clone of the actor started
+    Clone,
+    // the message and the names its payload gets bound to
+    Message(Located<String>, Vec<Located<String>>),
+    // fires when the condition becomes true
+    Condition(Box<Located<Condition>>),
+}
+impl Parsable for Trigger {
+    fn name() -> &'static str {
+        "trigger"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::Trigger)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: _, mut pos } = parser.token_expect(Token::Trigger)?;
+        let trigger = match parser.token_ref().map(|token| token.item.clone()) {
+            Some(Token::Word(word)) if word == "start" => {
+                parser.token();
+                Self::Start
+            }
+            Some(Token::Word(word)) if word == "clone" => {
+                parser.token();
+                Self::Clone
+            }
+            Some(Token::Word(_)) => {
+                let id = parser.word()?;
+                let mut params = vec![];
+                while let Some(Located { item: Token::Word(_), pos: _ }) = parser.token_ref() {
+                    params.push(parser.word()?);
+                }
+                Self::Message(id, params)
+            }
+            _ => Self::Condition(Box::new(Condition::parse(parser)?))
+        };
+        let Located { item: _, pos: end_pos } = parser.token_expect(Token::Trigger)?;
+        pos.extend(&end_pos);
+        Ok(Located::new(trigger, pos))
+    }
+}
 
 // |TRIGGER| BLOCK
 #[derive(Debug, Clone, PartialEq)]
 pub struct Event {
-    trigger: Trigger,
-    body: Block
+    pub trigger: Located<Trigger>,
+    pub body: Located<Block>
+}
+impl Event {
+    pub fn new(trigger: Located<Trigger>, body: Located<Block>) -> Self {
+        Self { trigger, body }
+    }
+}
+impl Parsable for Event {
+    fn name() -> &'static str {
+        "event"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::Trigger)
+    }
+    fn item() -> Option<Item> {
+        Some(Item::Event)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let trigger = Trigger::parse(parser)?;
+        parser.event = true;
+        let body = Block::parse(parser);
+        parser.event = false;
+        let body = body?;
+        let mut pos = trigger.pos.clone();
+        pos.extend(&body.pos);
+        Ok(Located::new(Self::new(trigger, body), pos))
+    }
 }
 
-// (ID)
+// (ID PARAM ...) where PARAM is ID or ID: TYPE
 #[derive(Debug, Clone, PartialEq)]
-pub struct Message(String);
+pub struct Message {
+    pub label: String,
+    pub params: Vec<Located<Param>>
+}
 impl Message {
-    pub fn new(label: String) -> Self {
-        Self(label)
+    pub fn new(label: String, params: Vec<Located<Param>>) -> Self {
+        Self { label, params }
+    }
+    pub fn label(&self) -> &String {
+        &self.label
     }
 }
 impl Parsable for Message {
+    fn name() -> &'static str {
+        "message declaration"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::ExprIn)
+    }
+    fn item() -> Option<Item> {
+        Some(Item::Message)
+    }
     fn parse(parser: &mut Parser) -> Result<Located<Self>, crate::error::Error> {
         let Located { item: _, mut pos } = parser.token_expect(Token::ExprIn)?;
-        let Located { item: token, pos: id_pos } = parser.token_check()?;
-        let Token::Word(id) = token else {
-            return Err(Error::new(format!("expected {}, got {}", Token::Word("".into()).name(), token.name()), parser.path.clone(), Some(id_pos)))
-        };
+        let Located { item: label, pos: _ } = parser.word()?;
+        let mut params = vec![];
+        while !parser.check(&Token::ExprOut) {
+            let id = parser.word()?;
+            let annotation = Param::parse_annotation(parser)?;
+            let mut param_pos = id.pos.clone();
+            if let Some(annotation) = &annotation {
+                param_pos.extend(&annotation.pos);
+            }
+            params.push(Located::new(Param::new(id, ValueType::Expression, annotation, None), param_pos));
+        }
         let Located { item: _, pos: end_pos } = parser.token_expect(Token::ExprOut)?;
         pos.extend(&end_pos);
-        Ok(Located::new(Self::new(id), pos))
+        Ok(Located::new(Self::new(label, params), pos))
     }
 }
 // (ID)
 // (ID = EXPR)
 #[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
-    id: Located<String>,
-    value: Option<Located<Expression>>,
+    pub id: Located<String>,
+    pub value: Option<Located<Expression>>,
 }
 impl Variable {
     pub fn new(id: Located<String>, value: Option<Located<Expression>>) -> Self {
@@ -109,36 +598,168 @@ impl Variable {
     }
 }
 impl Parsable for Variable {
+    fn name() -> &'static str {
+        "variable declaration"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::ExprIn)
+    }
+    fn item() -> Option<Item> {
+        Some(Item::Variable)
+    }
     fn parse(parser: &mut Parser) -> Result<Located<Self>, crate::error::Error> {
         let Located { item: _, mut pos } = parser.token_expect(Token::ExprIn)?;
-        let Located { item: token, pos: id_pos } = parser.token_check()?;
-        let Token::Word(id) = token else {
-            return Err(Error::new(format!("expected {}, got {}", Token::Word("".into()).name(), token.name()), parser.path.clone(), Some(id_pos)))
-        };
+        let Located { item: id, pos: id_pos } = parser.word()?;
         let mut value = None;
-        // if let Some(Located { item: Token::Equal, pos: _ }) = parser.token_ref() {
-        //     parser.token();
-        //     value = Some(Expression::parse(parser)?);
-        // }
+        if parser.check(&Token::Equal) {
+            parser.token();
+            value = Some(Expression::parse(parser)?);
+        }
         let Located { item: _, pos: end_pos } = parser.token_expect(Token::ExprOut)?;
         pos.extend(&end_pos);
         Ok(Located::new(Self::new(Located::new(id, id_pos), value), pos))
     }
 }
 
+// EXPR
+// COND
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Expression(Located<Expression>), Condition(Located<Condition>)
+}
+impl Argument {
+    pub fn typ(&self) -> ValueType {
+        match self {
+            Self::Expression(_) => ValueType::Expression,
+            Self::Condition(_) => ValueType::Condition,
+        }
+    }
+    pub fn pos(&self) -> &Position {
+        match self {
+            Self::Expression(expr) => &expr.pos,
+            Self::Condition(cond) => &cond.pos,
+        }
+    }
+    pub fn parse_typed(parser: &mut Parser, typ: ValueType) -> Result<Self, Error> {
+        match typ {
+            ValueType::Expression => Ok(Self::Expression(Expression::parse(parser)?)),
+            ValueType::Condition => Ok(Self::Condition(Condition::parse(parser)?)),
+        }
+    }
+    // identifiers are parsed as expressions
+    pub fn parse(parser: &mut Parser) -> Result<Self, Error> {
+        let typ = parser.token_ref().and_then(|token| ValueType::of(&token.item)).unwrap_or(ValueType::Expression);
+        Self::parse_typed(parser, typ)
+    }
+    pub fn parse_args(parser: &mut Parser, end: Token) -> Result<Vec<Self>, Error> {
+        let mut args = vec![];
+        while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
+            if token == &end { break; }
+            args.push(Self::parse(parser)?);
+        }
+        Ok(args)
+    }
+}
+
 // (ID)
+// (ID = EXPR)
+// (ID: TYPE)
+// (ID: TYPE = EXPR)
+// <ID>
+// <ID = COND>
 #[derive(Debug, Clone, PartialEq)]
 pub struct Param {
-    id: Located<String>,
-    typ: ValueType,
-    default: Located<Expression>
+    pub id: Located<String>,
+    pub typ: ValueType,
+    pub annotation: Option<Located<Type>>,
+    pub default: Option<Argument>
+}
+impl Param {
+    pub fn new(id: Located<String>, typ: ValueType, annotation: Option<Located<Type>>, default: Option<Argument>) -> Self {
+        Self { id, typ, annotation, default }
+    }
+    // a parameter is a bracketed identifier optionally followed by a default value,
+    // anything else ends the parameter list
+    pub fn is_next(parser: &Parser) -> bool {
+        matches!(
+            (parser.peek(0).map(|token| &token.item), parser.peek(1).map(|token| &token.item), parser.peek(2).map(|token| &token.item)),
+            (Some(Token::ExprIn), Some(Token::Word(_)), Some(Token::ExprOut | Token::Equal | Token::Rep)) |
+            (Some(Token::CondIn), Some(Token::Word(_)), Some(Token::CondOut | Token::Equal | Token::Rep))
+        )
+    }
+    // : TYPE
+    pub fn parse_annotation(parser: &mut Parser) -> Result<Option<Located<Type>>, Error> {
+        if !parser.check(&Token::Rep) {
+            return Ok(None)
+        }
+        parser.token();
+        let Located { item: name, pos } = parser.word()?;
+        let Some(typ) = Type::from_name(&name) else {
+            return Err(Error::new(format!("unknown type {name:?}"), parser.path.clone(), Some(pos)))
+        };
+        Ok(Some(Located::new(typ, pos)))
+    }
+    pub fn parse_params(parser: &mut Parser) -> Result<Vec<Located<Self>>, Error> {
+        let mut params = vec![];
+        while Self::is_next(parser) {
+            params.push(Self::parse(parser)?);
+        }
+        Ok(params)
+    }
+}
+impl Parsable for Param {
+    fn name() -> &'static str {
+        "parameter"
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: token, mut pos } = parser.token_check()?;
+        let (typ, end) = match token {
+            Token::ExprIn => (ValueType::Expression, Token::ExprOut),
+            Token::CondIn => (ValueType::Condition, Token::CondOut),
+            token => return Err(Error::new(format!("expected parameter, got {}", token.name()), parser.path.clone(), Some(pos)))
+        };
+        let id = parser.word()?;
+        let annotation = Self::parse_annotation(parser)?;
+        let mut default = None;
+        if parser.check(&Token::Equal) {
+            parser.token();
+            default = Some(Argument::parse_typed(parser, typ)?);
+        }
+        let Located { item: _, pos: end_pos } = parser.token_expect(end)?;
+        pos.extend(&end_pos);
+        Ok(Located::new(Self::new(id, typ, annotation, default), pos))
+    }
 }
 // #ID PARAMS BLOCK
 #[derive(Debug, Clone, PartialEq)]
 pub struct Procedure {
-    id: Located<String>,
-    params: Vec<Located<Param>>,
-    body: Block
+    pub id: Located<String>,
+    pub params: Vec<Located<Param>>,
+    pub body: Located<Block>
+}
+impl Procedure {
+    pub fn new(id: Located<String>, params: Vec<Located<Param>>, body: Located<Block>) -> Self {
+        Self { id, params, body }
+    }
+}
+impl Parsable for Procedure {
+    fn name() -> &'static str {
+        "procedure"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::Def)
+    }
+    fn item() -> Option<Item> {
+        Some(Item::Procedure)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: _, mut pos } = parser.token_expect(Token::Def)?;
+        let id = parser.word()?;
+        let params = Param::parse_params(parser)?;
+        let body = Block::parse(parser)?;
+        pos.extend(&body.pos);
+        Ok(Located::new(Self::new(id, params, body), pos))
+    }
 }
 // #(ID) PARAMS BLOCK
 // #(ID) PARAMS EXPR
@@ -146,92 +767,198 @@ pub struct Procedure {
 // #<ID> PARAMS COND
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function {
-    id: Located<String>,
-    params: Vec<Located<Param>>,
-    body: Block,
-    return_type: ValueType
+    pub id: Located<String>,
+    pub params: Vec<Located<Param>>,
+    pub body: Located<Block>,
+    pub return_type: ValueType
+}
+impl Function {
+    pub fn new(id: Located<String>, params: Vec<Located<Param>>, body: Located<Block>, return_type: ValueType) -> Self {
+        Self { id, params, body, return_type }
+    }
+    // an expression or condition body is turned into a block returning it
+    pub fn parse_body(parser: &mut Parser, return_type: ValueType) -> Result<Located<Block>, Error> {
+        if let Some(Located { item: Token::BodyIn, pos: _ }) = parser.token_ref() {
+            return Block::parse(parser)
+        }
+        let stat = match Argument::parse_typed(parser, return_type)? {
+            Argument::Expression(expr) => Located::new(Statement::Return(expr.clone()), expr.pos),
+            Argument::Condition(cond) => Located::new(Statement::ReturnCondition(cond.clone()), cond.pos),
+        };
+        let pos = stat.pos.clone();
+        Ok(Located::new(Block::new(vec![stat]), pos))
+    }
+}
+impl Parsable for Function {
+    fn name() -> &'static str {
+        "function"
+    }
+    fn first() -> Option<Token> {
+        Some(Token::Def)
+    }
+    fn item() -> Option<Item> {
+        Some(Item::Function)
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: _, mut pos } = parser.token_expect(Token::Def)?;
+        let (return_type, end) = if parser.check(&Token::ExprIn) {
+            (ValueType::Expression, Token::ExprOut)
+        } else if parser.check(&Token::CondIn) {
+            (ValueType::Condition, Token::CondOut)
+        } else {
+            return Err(parser.unexpected(&[], Some("after `#`")))
+        };
+        parser.token();
+        let id = parser.word()?;
+        parser.token_expect(end)?;
+        let params = Param::parse_params(parser)?;
+        let (loops, function) = (parser.loops, parser.function);
+        parser.loops = 0;
+        parser.function = Some(return_type);
+        let body = Self::parse_body(parser, return_type);
+        parser.loops = loops;
+        parser.function = function;
+        let body = body?;
+        pos.extend(&body.pos);
+        Ok(Located::new(Self::new(id, params, body, return_type), pos))
+    }
+}
+
+// the kind of an item that failed to parse, it is kept in place of the item by its actor or program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    Variable, Message, Event, Procedure, Function
 }
 
+// actor ID { data { ... } messages { ... } procedures { ... } functions { ... } events { ... } }
 #[derive(Debug, Clone, PartialEq)]
-pub struct Program {
-    // variables { ... }
-    variables: Vec<Located<Variable>>,
+pub struct Actor {
+    pub id: Located<String>,
+    // data { ... }
+    pub variables: Vec<Located<Variable>>,
     // messages { ... }
-    messages: Vec<Located<Message>>,
-    // chunks { ... }
-    events: Vec<Located<Event>>,
+    pub messages: Vec<Located<Message>>,
+    // events { ... }
+    pub events: Vec<Located<Event>>,
     // procedures { ... }
-    procedures: Vec<Located<Procedure>>,
+    pub procedures: Vec<Located<Procedure>>,
     // functions { ... }
-    functions: Vec<Located<Function>>,
+    pub functions: Vec<Located<Function>>,
+    // items of the sections that failed to parse
+    pub errors: Vec<Located<Item>>,
+}
+impl Actor {
+    pub fn new(id: Located<String>) -> Self {
+        Self { id, variables: vec![], messages: vec![], events: vec![], procedures: vec![], functions: vec![], errors: vec![] }
+    }
+    pub fn parse_section(&mut self, parser: &mut Parser) -> Result<(), Error> {
+        let Located { item: word, pos: word_pos } = parser.word()?;
+        match word.as_str() {
+            "data" => self.variables.append(&mut parser.body()?.item),
+            "messages" => self.messages.append(&mut parser.body()?.item),
+            "procedures" => self.procedures.append(&mut parser.body()?.item),
+            "functions" => self.functions.append(&mut parser.body()?.item),
+            "events" => self.events.append(&mut parser.body()?.item),
+            word => return Err(Error::new(format!("unexpected word {word:?}"), parser.path.clone(), Some(word_pos)))
+        }
+        Ok(())
+    }
+}
+impl Parsable for Actor {
+    fn name() -> &'static str {
+        "actor"
+    }
+    fn parse(parser: &mut Parser) -> Result<Located<Self>, Error> {
+        let Located { item: _, mut pos } = parser.word()?;
+        let mut actor = Actor::new(parser.word()?);
+        parser.token_expect(Token::BodyIn)?;
+        while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
+            // another actor means this one is missing its '}'
+            if token == &Token::BodyOut || matches!(token, Token::Word(word) if word == "actor") { break; }
+            let mark = parser.mark();
+            let result = actor.parse_section(parser);
+            let _ = parser.recover(result, mark);
+        }
+        actor.errors.append(&mut parser.broken);
+        match parser.token_ref() {
+            Some(Located { item: Token::BodyOut, pos: end_pos }) => {
+                pos.extend(end_pos);
+                parser.token();
+            }
+            _ => {
+                let err = parser.expected(&Token::BodyOut);
+                parser.errors.push(err);
+            }
+        }
+        Ok(Located::new(actor, pos))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    // data { ... }
+    pub variables: Vec<Located<Variable>>,
+    // messages { ... }
+    pub messages: Vec<Located<Message>>,
+    // actor ID { ... }
+    pub actors: Vec<Located<Actor>>,
+    // items of the global sections that failed to parse
+    pub errors: Vec<Located<Item>>,
 }
 impl Program {
     pub fn new() -> Self {
-        Self { variables: vec![], messages: vec![], events: vec![], procedures: vec![], functions: vec![] }
+        Self { variables: vec![], messages: vec![], actors: vec![], errors: vec![] }
+    }
+}
+impl Program {
+    pub fn parse_item(&mut self, parser: &mut Parser) -> Result<Position, Error> {
+        let Located { item: token, pos: start_pos } = parser.token_ref().cloned().expect("next token");
+        match token {
+            Token::Word(word) => match word.as_str() {
+                "data" => {
+                    parser.token();
+                    let Located { item: mut variables, pos } = parser.body()?;
+                    self.variables.append(&mut variables);
+                    Ok(pos)
+                }
+                "messages" => {
+                    parser.token();
+                    let Located { item: mut messages, pos } = parser.body()?;
+                    self.messages.append(&mut messages);
+                    Ok(pos)
+                }
+                "actor" => {
+                    let actor = Actor::parse(parser)?;
+                    let pos = actor.pos.clone();
+                    self.actors.push(actor);
+                    Ok(pos)
+                }
+                "procedures" | "functions" | "events" => Err(Error::new(format!("{word:?} have to be declared inside of an actor"), parser.path.clone(), Some(start_pos))),
+                word => Err(Error::new(format!("unexpected word {word:?}"), parser.path.clone(), Some(start_pos)))
+            }
+            token => Err(Error::new(format!("unexpected {}", token.name()), parser.path.clone(), Some(start_pos)))
+        }
     }
 }
 impl Parsable for Program {
+    fn name() -> &'static str {
+        "program"
+    }
+    // never fails, errors are collected in `parser.errors`
     fn parse(parser: &mut Parser) -> Result<Located<Self>, crate::error::Error> {
         let mut program = Program::new();
         let mut pos = Position::default();
-        while let Some(Located { item: token, pos: start_pos }) = parser.token() {
-            match token {
-                Token::Word(word) => match word.as_str() {
-                    "data" => {
-                        parser.token_expect(Token::BodyIn)?;
-                        while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
-                            if token == &Token::BodyOut { break; }
-                            program.variables.push(Variable::parse(parser)?);
-                            parser.token_skip(Token::End);
-                        }
-                        let Located { item: _, pos: end_pos } = parser.token_expect(Token::BodyOut)?;
-                        pos.extend(&end_pos)
-                    }
-                    "messages" => {
-                        parser.token_expect(Token::BodyIn)?;
-                        while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
-                            if token == &Token::BodyOut { break; }
-                            program.messages.push(Message::parse(parser)?);
-                            parser.token_skip(Token::End);
-                        }
-                        let Located { item: _, pos: end_pos } = parser.token_expect(Token::BodyOut)?;
-                        pos.extend(&end_pos)
-                    }
-                    // "procedures" => {
-                    //     parser.token_expect(Token::BodyIn)?;
-                    //     while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
-                    //         if token == &Token::BodyOut { break; }
-                    //         program.procedures.push(Procedure::parse(parser)?);
-                    //         parser.token_skip(Token::End);
-                    //     }
-                    //     let Located { item: _, pos: end_pos } = parser.token_expect(Token::BodyOut)?;
-                    //     pos.extend(&end_pos)
-                    // }
-                    // "functions" => {
-                    //     parser.token_expect(Token::BodyIn)?;
-                    //     while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
-                    //         if token == &Token::BodyOut { break; }
-                    //         program.functions.push(Function::parse(parser)?);
-                    //         parser.token_skip(Token::End);
-                    //     }
-                    //     let Located { item: _, pos: end_pos } = parser.token_expect(Token::BodyOut)?;
-                    //     pos.extend(&end_pos)
-                    // }
-                    // "events" => {
-                    //     parser.token_expect(Token::BodyIn)?;
-                    //     while let Some(Located { item: token, pos: _ }) = parser.token_ref() {
-                    //         if token == &Token::BodyOut { break; }
-                    //         program.events.push(Event::parse(parser)?);
-                    //         parser.token_skip(Token::End);
-                    //     }
-                    //     let Located { item: _, pos: end_pos } = parser.token_expect(Token::BodyOut)?;
-                    //     pos.extend(&end_pos)
-                    // }
-                    word => return Err(Error::new(format!("unexpected word {word:?}"), parser.path.clone(), Some(start_pos)))
-                }
-                token => return Err(Error::new(format!("unexpected {}", token.name()), parser.path.clone(), Some(start_pos)))
+        while parser.token_ref().is_some() {
+            let mark = parser.mark();
+            let result = program.parse_item(parser);
+            match parser.recover(result, mark) {
+                Ok(end_pos) => pos.extend(&end_pos),
+                Err(_) => while parser.token_ref().is_some() && !parser.at_sync_word() {
+                    parser.token();
+                }
             }
+            program.errors.append(&mut parser.broken);
         }
         Ok(Located::new(program, pos))
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,31 @@
-use crate::{location::{path::FilePath, position::Located}, lexer::token::Token, error::Error};
+use crate::{location::{path::FilePath, position::{Located, Position}}, lexer::token::Token, error::Error};
 
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod ast;
 
 use parser::{Parser, Parsable};
 use ast::Program;
 
-pub fn parse(path: &FilePath, tokens: Vec<Located<Token>>) -> Result<Located<Program>, Error> {
-    Program::parse(&mut Parser::new(path.clone(), tokens))
-}
\ No newline at end of file
+pub fn parse(path: &FilePath, tokens: Vec<Located<Token>>) -> Result<Located<Program>, Vec<Error>> {
+    let (program, errors) = parse_recovering(path, tokens);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+    Ok(program)
+}
+// the partial program is returned next to every syntax error
+pub fn parse_recovering(path: &FilePath, tokens: Vec<Located<Token>>) -> (Located<Program>, Vec<Error>) {
+    let mut parser = Parser::new(path.clone(), tokens);
+    let program = match Program::parse(&mut parser) {
+        Ok(program) => program,
+        Err(err) => {
+            parser.errors.push(err);
+            Located::new(Program::new(), Position::default())
+        }
+    };
+    (program, parser.errors)
+}
+
+#[cfg(test)]
+mod tests;
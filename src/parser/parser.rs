@@ -1,39 +1,209 @@
-use crate::{location::{path::FilePath, position::Located}, lexer::token::Token, error::Error, join};
+use crate::{location::{path::FilePath, position::{Located, Position}}, lexer::token::Token, error::Error};
+use super::ast::{ValueType, Item};
+
+// words starting a section or an actor, the parser resynchronizes on them after an error
+pub const SYNC_WORDS: [&str; 6] = ["data", "messages", "procedures", "functions", "events", "actor"];
 
 pub struct Parser {
     pub path: FilePath,
-    tokens: Vec<Located<Token>>
+    tokens: Vec<Located<Token>>,
+    // index of the next token
+    idx: usize,
+    // number of loops around the current statement
+    pub loops: usize,
+    // return type of the function around the current statement
+    pub function: Option<ValueType>,
+    // whether the current statement is in an event, where `[return]` replies to a request
+    pub event: bool,
+    // errors the parser recovered from
+    pub errors: Vec<Error>,
+    // items without an error node that failed to parse, taken by the actor or program around them
+    pub broken: Vec<Located<Item>>,
+    // tokens that would have been accepted at `expected_idx`
+    expected: Vec<Token>,
+    expected_idx: usize
 }
 impl Parser {
     pub fn new(path: FilePath, tokens: Vec<Located<Token>>) -> Self {
-        Self { path, tokens }
+        Self { path, tokens, idx: 0, loops: 0, function: None, event: false, errors: vec![], broken: vec![], expected: vec![], expected_idx: 0 }
     }
 
     pub fn token(&mut self) -> Option<Located<Token>> {
-        if self.tokens.len() > 0 { Some(self.tokens.remove(0)) } else { None }
+        let token = self.tokens.get(self.idx).cloned()?;
+        self.idx += 1;
+        Some(token)
     }
     pub fn token_ref(&self) -> Option<&Located<Token>> {
-        self.tokens.get(0)
+        self.tokens.get(self.idx)
+    }
+    // the token `n` tokens after the next one
+    pub fn peek(&self, n: usize) -> Option<&Located<Token>> {
+        self.tokens.get(self.idx + n)
+    }
+    // position to `reset` to for backtracking
+    pub fn mark(&self) -> usize {
+        self.idx
+    }
+    pub fn reset(&mut self, mark: usize) {
+        self.idx = mark;
+    }
+    // the position right after the last token, where errors at the end of input are reported
+    pub fn end_pos(&self) -> Position {
+        self.tokens.last().map(|token| token.pos.end()).unwrap_or_default()
+    }
+    // whether the next token is `token`, remembering it as acceptable for error messages
+    pub fn check(&mut self, token: &Token) -> bool {
+        if self.expected_idx != self.idx {
+            self.expected.clear();
+            self.expected_idx = self.idx;
+        }
+        if !self.expected.contains(token) {
+            self.expected.push(token.clone());
+        }
+        self.token_ref().is_some_and(|next| &next.item == token)
+    }
+    // `x` or one of `x`, `y`, ...
+    pub fn expected_list(&mut self, expects: &[Token]) -> String {
+        for expect in expects {
+            self.check(expect);
+        }
+        let names = self.expected.iter().map(Token::name).collect::<Vec<String>>();
+        if names.len() == 1 { names.join("") } else { format!("one of {}", names.join(", ")) }
+    }
+    // error for the next token not being one of `expects`, without consuming it
+    pub fn unexpected(&mut self, expects: &[Token], context: Option<&str>) -> Error {
+        let list = self.expected_list(expects);
+        let context = context.map(|context| format!(" {context}")).unwrap_or_default();
+        match self.token_ref() {
+            Some(token) => Error::new(format!("expected {list}{context}, got {}", token.item.name()), self.path.clone(), Some(token.pos.clone())),
+            None => Error::new(format!("expected {list}{context}, got end of input"), self.path.clone(), Some(self.end_pos()))
+        }
+    }
+    pub fn expected(&mut self, expect: &Token) -> Error {
+        self.unexpected(std::slice::from_ref(expect), None)
     }
     pub fn token_check(&mut self) -> Result<Located<Token>, Error> {
         let Some(token) = self.token() else {
-            return Err(Error::new("unexpected end of input", self.path.clone(), None))
+            return Err(Error::new("unexpected end of input", self.path.clone(), Some(self.end_pos())))
         };
         Ok(token)
     }
     pub fn token_expect(&mut self, expect: Token) -> Result<Located<Token>, Error> {
-        let token = self.token_check()?;
-        if token.item != expect {
-            return Err(Error::new(format!("expected {}, got {}", expect.name(), token.item.name()), self.path.clone(), Some(token.pos)))
+        if !self.check(&expect) {
+            return Err(self.expected(&expect))
         }
-        Ok(token)
+        self.token_check()
     }
     pub fn token_expects(&mut self, expects: Vec<Token>) -> Result<Located<Token>, Error> {
-        let token = self.token_check()?;
-        if expects.contains(&token.item) {
-            return Err(Error::new(format!("expected {}, got {}", join!(expects, "/"), token.item.name()), self.path.clone(), Some(token.pos)))
+        if !expects.iter().any(|expect| self.check(expect)) {
+            return Err(self.unexpected(&expects, None))
+        }
+        self.token_check()
+    }
+    pub fn word(&mut self) -> Result<Located<String>, Error> {
+        match self.token_ref().cloned() {
+            Some(Located { item: Token::Word(word), pos }) => {
+                self.token();
+                Ok(Located::new(word, pos))
+            }
+            _ => Err(self.expected(&Token::Word("".into())))
         }
-        Ok(token)
+    }
+    pub fn at_sync_word(&self) -> bool {
+        matches!(self.token_ref(), Some(Located { item: Token::Word(word), pos: _ }) if SYNC_WORDS.contains(&word.as_str()))
+    }
+    // skips the rest of a broken item starting at `mark` up to the next `;`, `}` or section word
+    // while keeping brackets balanced, at least one token is skipped
+    pub fn synchronize(&mut self, mark: usize) -> Position {
+        self.reset(mark);
+        let mut pos = self.token_ref().map(|token| token.pos.clone()).unwrap_or_default();
+        let mut stack = vec![];
+        while let Some(Located { item: token, pos: token_pos }) = self.token_ref().cloned() {
+            let skipped = self.idx > mark;
+            match token {
+                Token::ExprIn => stack.push(Token::ExprOut),
+                Token::CondIn => stack.push(Token::CondOut),
+                Token::StatIn => stack.push(Token::StatOut),
+                Token::BodyIn => stack.push(Token::BodyOut),
+                Token::ExprOut | Token::CondOut | Token::StatOut | Token::BodyOut => {
+                    // a closing bracket also closes the unclosed brackets inside of it
+                    if stack.contains(&token) {
+                        while stack.pop() != Some(token.clone()) {}
+                        if stack.is_empty() {
+                            self.token();
+                            pos.extend(&token_pos);
+                            break;
+                        }
+                    } else if token == Token::BodyOut && !stack.contains(&Token::BodyOut) && skipped {
+                        break;
+                    }
+                }
+                Token::End if stack.is_empty() => {
+                    self.token();
+                    pos.extend(&token_pos);
+                    break;
+                }
+                Token::Word(_) if stack.is_empty() && skipped && self.at_sync_word() => break,
+                _ => {}
+            }
+            self.token();
+            pos.extend(&token_pos);
+        }
+        self.expected.clear();
+        pos
+    }
+    // records the error of a failed parse and synchronizes
+    pub fn recover<T>(&mut self, result: Result<T, Error>, mark: usize) -> Result<T, Position> {
+        result.map_err(|err| {
+            self.errors.push(err);
+            self.synchronize(mark)
+        })
+    }
+    // { ITEM; ITEM; ... }
+    // broken items are replaced with `Parsable::error` or recorded as `Parsable::item` for the actor or program
+    pub fn body<T: Parsable>(&mut self) -> Result<Located<Vec<Located<T>>>, Error> {
+        let Located { item: _, mut pos } = self.token_expect(Token::BodyIn)?;
+        let mut items = vec![];
+        // whether the last item wasn't followed by a separator yet
+        let mut after_item = false;
+        while self.token_ref().is_some() {
+            if self.check(&Token::BodyOut) || self.at_sync_word() { break; }
+            let mark = self.mark();
+            let result = T::parse(self);
+            match self.recover(result, mark) {
+                Ok(item) => {
+                    items.push(item);
+                    after_item = true;
+                }
+                Err(pos) => if let Some(error) = T::error() {
+                    items.push(Located::new(error, pos));
+                } else if let Some(item) = T::item() {
+                    self.broken.push(Located::new(item, pos));
+                }
+            }
+            if self.check(&Token::End) {
+                self.token();
+                after_item = false;
+                continue;
+            }
+            // the separator is optional before the next item
+            let Some(first) = T::first() else { continue };
+            if self.check(&Token::BodyOut) || self.token_ref().is_none() || self.check(&first) { continue; }
+            let err = self.unexpected(&[], Some(&format!("after {}", T::name())));
+            self.errors.push(err);
+            let mark = self.mark();
+            self.synchronize(mark);
+            after_item = false;
+        }
+        if self.check(&Token::BodyOut) {
+            let Located { item: _, pos: end_pos } = self.token_check()?;
+            pos.extend(&end_pos);
+        } else {
+            let context = after_item.then(|| format!("after {}", T::name()));
+            let err = self.unexpected(&[Token::BodyOut], context.as_deref());
+            self.errors.push(err);
+        }
+        Ok(Located::new(items, pos))
     }
     pub fn token_skip(&mut self, expect: Token) {
         let Some(token) = self.token_ref() else { return };
@@ -45,7 +215,29 @@ impl Parser {
 
 pub trait Parsable where Self: Sized {
     fn parse(parser: &mut Parser) -> Result<Located<Self>, Error>;
-    fn can_parse(parser: &mut Parser) -> Option<Located<Self>> {
+    // what the item is called in error messages
+    fn name() -> &'static str;
+    // the token every item starts with, if there is a single one
+    fn first() -> Option<Token> {
+        None
+    }
+    // node standing in for an item that failed to parse
+    fn error() -> Option<Self> {
+        None
+    }
+    // kind of the item recorded next to the items when it has no error node
+    fn item() -> Option<Item> {
         None
     }
+    // parses speculatively, nothing is consumed and no error is kept if parsing fails
+    fn can_parse(parser: &mut Parser) -> Option<Located<Self>> {
+        let mark = parser.mark();
+        let errors = parser.errors.len();
+        let result = Self::parse(parser).ok();
+        if result.is_none() {
+            parser.reset(mark);
+            parser.errors.truncate(errors);
+        }
+        result
+    }
 }
\ No newline at end of file
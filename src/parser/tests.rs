@@ -0,0 +1,285 @@
+use crate::{location::{path::FilePath, position::Located}, lexer, testing::located};
+use super::{parse_recovering, parser::{Parser, Parsable}, ast::*};
+
+fn parser(text: &str) -> Parser {
+    let path = FilePath::Input(text.to_string());
+    Parser::new(path.clone(), lexer::lex(&path, text.to_string()).unwrap())
+}
+// the item as its `Debug` text, which leaves out positions
+fn item<T: Parsable + std::fmt::Debug>(text: &str) -> String {
+    let mut parser = parser(text);
+    let item = T::parse(&mut parser).unwrap();
+    assert!(parser.token_ref().is_none(), "{text:?} wasn't parsed completely");
+    format!("{item:?}")
+}
+// the error of parsing the item
+fn item_error<T: Parsable>(text: &str) -> String {
+    located(&T::parse(&mut parser(text)).err().expect("a syntax error"))
+}
+fn program(text: &str) -> Located<Program> {
+    let path = FilePath::Input(text.to_string());
+    let (program, errors) = parse_recovering(&path, lexer::lex(&path, text.to_string()).unwrap());
+    assert_eq!(errors, vec![], "{text:?} has syntax errors");
+    program
+}
+// every error of parsing a whole program
+fn errors(text: &str) -> Vec<String> {
+    let path = FilePath::Input(text.to_string());
+    let (_, errors) = parse_recovering(&path, lexer::lex(&path, text.to_string()).unwrap());
+    errors.iter().map(located).collect()
+}
+
+#[test]
+fn expressions() {
+    assert_eq!(item::<Expression>("x"), "ID(\"x\")");
+    assert_eq!(item::<Expression>("1.5"), "Number(1.5)");
+    assert_eq!(item::<Expression>("\"a\""), "String(\"a\")");
+    assert_eq!(item::<Expression>("(add 1 (mul x 2))"), "Add(Number(1.0), Mul(ID(\"x\"), Number(2.0)))");
+    assert_eq!(item::<Expression>("(substring s 1 2)"), "SubString(ID(\"s\"), Number(1.0), Number(2.0))");
+    assert_eq!(item::<Expression>("((1))"), "Number(1.0)");
+    assert_eq!(item::<Expression>("(f 1 <true>)"), "Call(\"f\", [Expression(Number(1.0)), Condition(True)])");
+}
+
+// `(x)` is a call without arguments to the parser, the resolver decides if it names a variable
+#[test]
+fn bracketed_identifiers_are_calls() {
+    assert_eq!(item::<Expression>("(x)"), "Call(\"x\", [])");
+}
+
+#[test]
+fn bad_expressions() {
+    assert_eq!(item_error::<Expression>("(add 1)"), "1:1: \"add\" expects 2 arguments, got 1");
+    assert_eq!(item_error::<Expression>("(add 1 2"), "1:9: expected `)`, got end of input");
+    assert_eq!(item_error::<Expression>("<true>"), "1:1: expected expression, got condition");
+    assert_eq!(item_error::<Expression>("]"), "1:1: expected expression, got `]`");
+}
+
+#[test]
+fn variable_initializers() {
+    let program = program("data { (x) (y = (add x 1)) }");
+    let variables = program.item.variables.iter().map(|var| format!("{:?} {:?}", var.item.id, var.item.value)).collect::<Vec<String>>();
+    assert_eq!(variables, vec!["\"x\" None", "\"y\" Some(Add(ID(\"x\"), Number(1.0)))"]);
+}
+
+#[test]
+fn conditions() {
+    assert_eq!(item::<Condition>("true"), "True");
+    assert_eq!(item::<Condition>("b"), "ID(\"b\")");
+    assert_eq!(item::<Condition>("<and <not b> <lt x 2>>"), "And(Not(ID(\"b\")), LT(ID(\"x\"), Number(2.0)))");
+    assert_eq!(item::<Condition>("<eq (add 1 2) \"3\">"), "EQ(Add(Number(1.0), Number(2.0)), String(\"3\"))");
+    assert_eq!(item::<Condition>("<<false>>"), "False");
+    assert_eq!(item::<Condition>("<even 4>"), "Call(\"even\", [Expression(Number(4.0))])");
+}
+
+#[test]
+fn bad_conditions() {
+    assert_eq!(item_error::<Condition>("<not a b>"), "1:1: \"not\" expects 1 arguments, got 2");
+    assert_eq!(item_error::<Condition>("<and (x) b>"), "1:6: expected condition, got expression");
+    assert_eq!(item_error::<Condition>("<lt 1 2"), "1:8: expected `>`, got end of input");
+}
+
+#[test]
+fn statements() {
+    assert_eq!(item::<Statement>("[set x (add x 1)]"), "Set(\"x\", Add(ID(\"x\"), Number(1.0)))");
+    assert_eq!(item::<Statement>("[set b <not b>]"), "SetCondition(\"b\", Not(ID(\"b\")))");
+    assert_eq!(item::<Statement>("[if b { [move] }]"), "If(ID(\"b\"), Block { statements: [Call(\"move\", [])] })");
+    assert_eq!(
+        item::<Statement>("[if b {} else { [stop 1] }]"),
+        "IfElse(ID(\"b\"), Block { statements: [] }, Block { statements: [Call(\"stop\", [Expression(Number(1.0))])] })"
+    );
+    assert_eq!(item::<Statement>("[repeat 3 { [break] }]"), "Repeat(Number(3.0), Block { statements: [Break] })");
+    assert_eq!(item::<Statement>("[while <lt x 3> { [next] }]"), "While(LT(ID(\"x\"), Number(3.0)), Block { statements: [Next] })");
+    assert_eq!(item::<Statement>("[loop { [loop { [break] }] [break] }]"), "Loop(Block { statements: [Loop(Block { statements: [Break] }), Break] })");
+}
+
+#[test]
+fn else_needs_a_block() {
+    assert_eq!(item_error::<Statement>("[if b {} else]"), "1:14: expected `{` after `else`, got `]`");
+    assert_eq!(item_error::<Statement>("[if b {} else"), "1:14: expected `{` after `else`, got end of input");
+    assert_eq!(item_error::<Statement>("[if b {} {}]"), "1:10: expected `]`, got `{`");
+}
+
+#[test]
+fn statements_out_of_context() {
+    assert_eq!(item_error::<Statement>("[break]"), "1:2: \"break\" outside of a loop");
+    assert_eq!(item_error::<Statement>("[next]"), "1:2: \"next\" outside of a loop");
+    assert_eq!(item_error::<Statement>("[return 1]"), "1:2: \"return\" outside of a function or event");
+}
+
+#[test]
+fn procedures() {
+    assert_eq!(
+        item::<Procedure>("#move (dx) (dy: number = 1) <fast = true> { [set x dx] }"),
+        "Procedure { id: \"move\", params: [\
+            Param { id: \"dx\", typ: Expression, annotation: None, default: None }, \
+            Param { id: \"dy\", typ: Expression, annotation: Some(Number), default: Some(Expression(Number(1.0))) }, \
+            Param { id: \"fast\", typ: Condition, annotation: None, default: Some(Condition(True)) }\
+        ], body: Block { statements: [Set(\"x\", ID(\"dx\"))] } }"
+    );
+}
+
+#[test]
+fn functions() {
+    // an expression or condition body returns it
+    assert_eq!(
+        item::<Function>("#(double) (n) (mul n 2)"),
+        "Function { id: \"double\", params: [Param { id: \"n\", typ: Expression, annotation: None, default: None }], \
+        body: Block { statements: [Return(Mul(ID(\"n\"), Number(2.0)))] }, return_type: Expression }"
+    );
+    assert_eq!(
+        item::<Function>("#<small> (n) <lt n 3>"),
+        "Function { id: \"small\", params: [Param { id: \"n\", typ: Expression, annotation: None, default: None }], \
+        body: Block { statements: [ReturnCondition(LT(ID(\"n\"), Number(3.0)))] }, return_type: Condition }"
+    );
+    assert_eq!(
+        item::<Function>("#(one) { [repeat 2 { [return 1] }] }"),
+        "Function { id: \"one\", params: [], body: Block { statements: [Repeat(Number(2.0), Block { statements: [Return(Number(1.0))] })] }, \
+        return_type: Expression }"
+    );
+}
+
+#[test]
+fn bad_functions() {
+    assert_eq!(item_error::<Function>("#f (n) 1"), "1:2: expected one of `(`, `<` after `#`, got identifier");
+    assert_eq!(item_error::<Function>("#<f> (n) (add n 1)"), "1:10: expected condition, got expression");
+    assert_eq!(errors("actor A { functions { #(f) { [return <true>] } } }"), vec!["1:38: expected expression to return, got condition"]);
+    assert_eq!(item_error::<Param>("(n: text)"), "1:5: unknown type \"text\"");
+    // a loop around the call doesn't allow `[break]` inside of the function
+    let mut parser = parser("#(f) { [break] }");
+    parser.loops = 1;
+    Function::parse(&mut parser).unwrap();
+    assert_eq!(parser.errors.iter().map(ToString::to_string).collect::<Vec<String>>(), vec!["<input>:1:9: \"break\" outside of a loop"]);
+}
+
+#[test]
+fn events() {
+    assert_eq!(item::<Event>("|start| {}"), "Event { trigger: Start, body: Block { statements: [] } }");
+    assert_eq!(
+        item::<Event>("|hit amount from| { [return amount] }"),
+        "Event { trigger: Message(\"hit\", [\"amount\", \"from\"]), body: Block { statements: [Return(ID(\"amount\"))] } }"
+    );
+    assert_eq!(item::<Event>("|<gt x 3>| {}"), "Event { trigger: Condition(GT(ID(\"x\"), Number(3.0))), body: Block { statements: [] } }");
+    assert_eq!(item_error::<Event>("|start {}"), "1:8: expected `|`, got `{`");
+    assert_eq!(item_error::<Event>("|(x)| {}"), "1:2: expected condition, got expression");
+}
+
+#[test]
+fn actors() {
+    let program = program(r#"
+        data { (score = 0) }
+        messages { (reset) }
+        actor player {
+            data { (x = 0); (y) }
+            messages { (hit amount: number) }
+            procedures { #move (dx) { [set x (add x dx)] } }
+            functions { #(double) (n) (mul n 2) }
+            events { |start| { [move (double 5)] } |reset| { [set x 0] } }
+            events { |hit a| {} }
+        }
+        actor enemy {}
+    "#).item;
+    assert_eq!(program.variables.len(), 1);
+    assert_eq!(program.messages[0].item.label(), "reset");
+    let actors = program.actors.iter().map(|actor| {
+        let actor = &actor.item;
+        (actor.id.item.as_str(), actor.variables.len(), actor.messages.len(), actor.procedures.len(), actor.functions.len(), actor.events.len())
+    }).collect::<Vec<_>>();
+    assert_eq!(actors, vec![("player", 2, 1, 1, 1, 3), ("enemy", 0, 0, 0, 0, 0)]);
+}
+
+#[test]
+fn sections_outside_of_actors() {
+    assert_eq!(errors("events { |start| {} }"), vec!["1:1: \"events\" have to be declared inside of an actor"]);
+    assert_eq!(errors("actor A { stuff {} }"), vec!["1:11: unexpected word \"stuff\""]);
+    assert_eq!(errors("actor A { data {} actor B {}"), vec!["1:19: expected `}`, got identifier"]);
+}
+
+#[test]
+fn cursor_looks_ahead_and_backtracks() {
+    let mut parser = parser("a (b) c");
+    assert_eq!(parser.peek(1).map(|token| token.item.to_string()), Some("(".to_string()));
+    let mark = parser.mark();
+    assert_eq!(parser.word().unwrap().item, "a");
+    assert!(parser.word().is_err());
+    parser.reset(mark);
+    assert_eq!(parser.word().unwrap().item, "a");
+    assert_eq!(format!("{:?}", Expression::parse(&mut parser).unwrap()), "Call(\"b\", [])");
+    assert_eq!(parser.word().unwrap().item, "c");
+    assert!(parser.token_ref().is_none() && parser.peek(5).is_none());
+}
+
+#[test]
+fn failed_speculative_parses_consume_nothing() {
+    let mut parser = parser("(add 1 <true>) [if b { [set x (] } else] x");
+    assert!(Expression::can_parse(&mut parser).is_none());
+    assert!(Condition::can_parse(&mut parser).is_none());
+    assert_eq!(parser.mark(), 0);
+    parser.reset(7);
+    // the error recovered from inside of the block is dropped with the failed statement
+    assert!(Statement::can_parse(&mut parser).is_none());
+    assert_eq!((parser.mark(), parser.errors.len()), (7, 0));
+    let mut parser = self::parser("(add 1 2) x");
+    assert_eq!(format!("{:?}", Expression::can_parse(&mut parser).unwrap()), "Add(Number(1.0), Number(2.0))");
+    assert_eq!(parser.word().unwrap().item, "x");
+}
+
+#[test]
+fn every_syntax_error_is_collected() {
+    assert_eq!(errors("actor A { data { (x = ); (y = ]) } events { |start| { [set x (]; [wait] } } }"), vec![
+        "1:23: expected expression, got `)`",
+        "1:31: expected expression, got `]`",
+        "1:63: expected expression, got `]`",
+        "1:71: expected expression, got `]`",
+    ]);
+}
+
+#[test]
+fn recovery_resumes_after_the_broken_item() {
+    let actor = |text: &str| {
+        let path = FilePath::Input(text.to_string());
+        let (program, errors) = parse_recovering(&path, lexer::lex(&path, text.to_string()).unwrap());
+        assert_eq!(errors.len(), 1, "{text:?}");
+        format!("{:?}", program.item.actors[0].item)
+    };
+    // on `;`
+    assert_eq!(actor("actor A { data { (x = 1); (y = ); (z = 2) } }"), "Actor { id: \"A\", variables: [Variable { id: \"x\", value: Some(Number(1.0)) }, Variable { id: \"z\", value: Some(Number(2.0)) }], messages: [], events: [], procedures: [], functions: [], errors: [Variable] }");
+    // on a missing separator
+    assert_eq!(actor("actor A { data { (x = 1) 5; (z = 2) } }"), "Actor { id: \"A\", variables: [Variable { id: \"x\", value: Some(Number(1.0)) }, Variable { id: \"z\", value: Some(Number(2.0)) }], messages: [], events: [], procedures: [], functions: [], errors: [] }");
+    // on `}` and the next section word
+    assert_eq!(actor("actor A { data { (x = ] } events { |start| {} } }"), "Actor { id: \"A\", variables: [], messages: [], events: [Event { trigger: Start, body: Block { statements: [] } }], procedures: [], functions: [], errors: [Variable] }");
+    assert_eq!(actor("actor A { data { (x = 1 } messages { (m) } }"), "Actor { id: \"A\", variables: [], messages: [Message { label: \"m\", params: [] }], events: [], procedures: [], functions: [], errors: [Variable] }");
+}
+
+#[test]
+fn broken_statements_become_error_nodes() {
+    let text = "actor A { procedures { #p { [set x 1]; [set x (]; [wait 1] } #q {} } }";
+    let path = FilePath::Input(text.to_string());
+    let (program, errors) = parse_recovering(&path, lexer::lex(&path, text.to_string()).unwrap());
+    assert_eq!(errors.len(), 1);
+    let procedures = program.item.actors[0].item.procedures.iter().map(|procedure| format!("{:?}", procedure.item.body.item)).collect::<Vec<String>>();
+    assert_eq!(procedures, vec!["Block { statements: [Set(\"x\", Number(1.0)), Error, Wait(Number(1.0))] }", "Block { statements: [] }"]);
+}
+
+#[test]
+fn broken_items_are_kept_in_their_place() {
+    let text = "data { (x = ) } actor A { messages { (m) (5) } procedures { #p {} #9 {} } functions { #f (1) } events { |start {} } }";
+    let path = FilePath::Input(text.to_string());
+    let (program, errors) = parse_recovering(&path, lexer::lex(&path, text.to_string()).unwrap());
+    assert_eq!(errors.len(), 5);
+    let items = |items: &[Located<Item>]| items.iter().map(|item| format!("{:?} {}", item.item, &text[item.pos.idx.clone()])).collect::<Vec<String>>();
+    assert_eq!(items(&program.item.errors), vec!["Variable (x = )"]);
+    assert_eq!(items(&program.item.actors[0].item.errors), vec!["Message (5)", "Procedure #9 {}", "Function #f (1)", "Event |start {}"]);
+}
+
+#[test]
+fn unclosed_bodies_name_the_last_item() {
+    assert_eq!(errors("data { (x = 1) 5 }"), vec!["1:16: expected one of `;`, `}`, `(` after variable declaration, got number"]);
+    assert_eq!(errors("data { (x = 1)"), vec!["1:15: expected one of `;`, `}` after variable declaration, got end of input"]);
+    assert_eq!(errors("data { (x = 1);"), vec!["1:16: expected `}`, got end of input"]);
+    assert_eq!(errors("actor A { events { |start| { [wait 1]"), vec![
+        "1:38: expected one of `;`, `}` after statement, got end of input",
+        "1:38: expected one of `;`, `}` after event, got end of input",
+        "1:38: expected one of `;`, `}`, got end of input",
+    ]);
+    assert_eq!(errors("data {"), vec!["1:7: expected `}`, got end of input"]);
+}
@@ -0,0 +1,407 @@
+use std::{collections::HashMap, cmp::Ordering, sync::{Arc, Mutex, MutexGuard, PoisonError}};
+use crate::{location::{path::FilePath, position::{Located, Position}}, error::Error, parser::ast::*};
+use crate::analysis::{symbol::{Symbol, Resolution}, checker::Slot};
+use super::value::Value;
+
+// nested calls allowed before a runaway recursion is reported
+pub const MAX_DEPTH: usize = 256;
+// stack of threads running scripts, enough for `MAX_DEPTH` nested calls in a debug build
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// how a statement left its block
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    Normal, Break, Next, Return(Value)
+}
+
+// a variable that was set and the value it had before
+#[derive(Debug, Clone, PartialEq)]
+pub enum Write {
+    Global(usize, Value),
+    // instance and index into its variables
+    Local(usize, usize, Value)
+}
+
+// evaluates a checked program, every identifier is looked up through the resolution
+pub struct Interpreter<'a> {
+    pub path: FilePath,
+    pub program: &'a Program,
+    pub resolution: &'a Resolution,
+    // shared with every interpreter split off this one
+    pub globals: Arc<Mutex<Vec<Value>>>,
+    // variables of every instance, the first instances are the actors themselves and clones follow
+    pub locals: Vec<Vec<Value>>,
+    // actor of every instance, `None` where a clone was deleted
+    pub instances: Vec<Option<usize>>,
+    // the instance actor variables are looked up in
+    pub instance: usize,
+    // current number of nested calls
+    depth: usize,
+    // every write since the journal was started, so it can be undone, `None` if writes aren't kept
+    pub journal: Option<Vec<Write>>
+}
+impl<'a> Interpreter<'a> {
+    pub fn new(path: FilePath, program: &'a Program, resolution: &'a Resolution, types: &HashMap<Slot, Type>) -> Self {
+        let default = |symbol| Value::default(types.get(&Slot::Variable(symbol)).copied().unwrap_or(Type::Number));
+        let globals = Arc::new(Mutex::new((0..program.variables.len()).map(|idx| default(Symbol::Global(idx))).collect()));
+        let locals = program.actors.iter().enumerate()
+            .map(|(actor, Located { item: Actor { variables, .. }, pos: _ })| (0..variables.len()).map(|idx| default(Symbol::Local(actor, idx))).collect())
+            .collect();
+        let instances = (0..program.actors.len()).map(Some).collect();
+        Self { path, program, resolution, globals, locals, instances, instance: 0, depth: 0, journal: None }
+    }
+    pub fn error<S: ToString>(&self, msg: S, pos: &Position) -> Error {
+        Error::new(msg, self.path.clone(), Some(pos.clone()))
+    }
+    // an interpreter for another thread running the given actors, it takes the variables of their instances along
+    pub fn split(&mut self, actors: &[usize]) -> Self {
+        let mut locals = vec![vec![]; self.locals.len()];
+        for (instance, actor) in self.instances.iter().enumerate() {
+            if actor.is_some_and(|actor| actors.contains(&actor)) {
+                locals[instance] = std::mem::take(&mut self.locals[instance]);
+            }
+        }
+        Self {
+            path: self.path.clone(), program: self.program, resolution: self.resolution, globals: Arc::clone(&self.globals),
+            locals, instances: self.instances.clone(), instance: 0, depth: 0, journal: None
+        }
+    }
+    // takes back the variables of an interpreter that was split off
+    pub fn join(&mut self, other: Self) {
+        for (locals, other) in self.locals.iter_mut().zip(other.locals) {
+            if !other.is_empty() {
+                *locals = other;
+            }
+        }
+    }
+    // number of clones that weren't deleted
+    pub fn clones(&self) -> usize {
+        self.instances[self.program.actors.len()..].iter().flatten().count()
+    }
+    // adds an instance of the actor with the given variables, in the place of a deleted clone if there is one
+    pub fn clone_actor(&mut self, actor: usize, locals: Vec<Value>) -> usize {
+        let actors = self.program.actors.len();
+        match self.instances[actors..].iter().position(Option::is_none) {
+            Some(idx) => {
+                self.instances[actors + idx] = Some(actor);
+                self.locals[actors + idx] = locals;
+                actors + idx
+            }
+            None => {
+                self.instances.push(Some(actor));
+                self.locals.push(locals);
+                self.instances.len() - 1
+            }
+        }
+    }
+    pub fn delete(&mut self, instance: usize) {
+        self.instances[instance] = None;
+        self.locals[instance].clear();
+    }
+    pub fn globals(&self) -> MutexGuard<'_, Vec<Value>> {
+        self.globals.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    // every variable with its current value, actor variables are prefixed with the actor's name, clones are left out
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables = vec![];
+        for (var, value) in self.program.variables.iter().zip(self.globals().iter()) {
+            variables.push((var.item.id.item.clone(), value.clone()));
+        }
+        for (actor, locals) in self.program.actors.iter().zip(&self.locals) {
+            for (var, value) in actor.item.variables.iter().zip(locals) {
+                variables.push((format!("{}.{}", actor.item.id.item, var.item.id.item), value.clone()));
+            }
+        }
+        variables
+    }
+
+    // runs the initializer of every variable, globals first and each in declaration order
+    pub fn init(&mut self) -> Result<(), Error> {
+        let program = self.program;
+        for (idx, var) in program.variables.iter().enumerate() {
+            let Some(value) = &var.item.value else { continue };
+            let value = self.expression(value, &[])?;
+            self.globals()[idx] = value;
+        }
+        for (actor, Located { item: Actor { variables, .. }, pos: _ }) in program.actors.iter().enumerate() {
+            self.instance = actor;
+            for (idx, var) in variables.iter().enumerate() {
+                let Some(value) = &var.item.value else { continue };
+                self.locals[actor][idx] = self.expression(value, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn variable(&self, pos: &Position, params: &[Value]) -> Result<Value, Error> {
+        match self.resolution.get(pos) {
+            Some(Symbol::Global(idx)) => Ok(self.globals()[idx].clone()),
+            Some(Symbol::Local(_, idx)) => Ok(self.locals[self.instance][idx].clone()),
+            Some(Symbol::Param(idx)) => params.get(idx).cloned().ok_or_else(|| self.error("parameter has no value", pos)),
+            _ => Err(self.error("unresolved variable", pos))
+        }
+    }
+    pub fn set(&mut self, pos: &Position, value: Value) -> Result<(), Error> {
+        let write = match self.resolution.get(pos) {
+            Some(Symbol::Global(idx)) => Write::Global(idx, std::mem::replace(&mut self.globals()[idx], value)),
+            Some(Symbol::Local(_, idx)) => Write::Local(self.instance, idx, std::mem::replace(&mut self.locals[self.instance][idx], value)),
+            _ => return Err(self.error("unresolved variable", pos))
+        };
+        if let Some(journal) = &mut self.journal {
+            journal.push(write);
+        }
+        Ok(())
+    }
+    // sets the variables back to what they were before the first `len` writes of the journal
+    pub fn undo(&mut self, len: usize) {
+        let Some(mut journal) = self.journal.take() else { return };
+        for write in journal.drain(len..).rev() {
+            match write {
+                Write::Global(idx, value) => self.globals()[idx] = value,
+                Write::Local(instance, idx, value) => self.locals[instance][idx] = value,
+            }
+        }
+        self.journal = Some(journal);
+    }
+    pub fn number(&mut self, expr: &Located<Expression>, params: &[Value]) -> Result<f64, Error> {
+        match self.expression(expr, params)? {
+            Value::Number(number) => Ok(number),
+            value => Err(self.error(format!("expected number, got {}", value.typ()), &expr.pos))
+        }
+    }
+    pub fn string(&mut self, expr: &Located<Expression>, params: &[Value]) -> Result<String, Error> {
+        match self.expression(expr, params)? {
+            Value::String(string) => Ok(string),
+            value => Err(self.error(format!("expected string, got {}", value.typ()), &expr.pos))
+        }
+    }
+    // indices into strings count chars and start at 1
+    pub fn index(&mut self, expr: &Located<Expression>, len: usize, params: &[Value]) -> Result<usize, Error> {
+        let idx = self.number(expr, params)?;
+        if idx.fract() != 0. || idx < 1. || idx > len as f64 {
+            return Err(self.error(format!("index {idx} out of range for string of length {len}"), &expr.pos))
+        }
+        Ok(idx as usize - 1)
+    }
+
+    pub fn expression(&mut self, expr: &Located<Expression>, params: &[Value]) -> Result<Value, Error> {
+        let value = match &expr.item {
+            Expression::ID(_) => return self.variable(&expr.pos, params),
+            Expression::Number(number) => Value::Number(*number),
+            Expression::String(string) => Value::String(string.clone()),
+            Expression::Add(left, right) => Value::Number(self.number(left, params)? + self.number(right, params)?),
+            Expression::Sub(left, right) => Value::Number(self.number(left, params)? - self.number(right, params)?),
+            Expression::Mul(left, right) => Value::Number(self.number(left, params)? * self.number(right, params)?),
+            Expression::Div(left, right) => {
+                let (left, right) = (self.number(left, params)?, self.number(right, params)?);
+                if right == 0. {
+                    return Err(self.error("division by zero", &expr.pos))
+                }
+                Value::Number(left / right)
+            }
+            Expression::Pow(left, right) => Value::Number(self.number(left, params)?.powf(self.number(right, params)?)),
+            // the result has the sign of the divisor, like in Scratch
+            Expression::Mod(left, right) => {
+                let (left, right) = (self.number(left, params)?, self.number(right, params)?);
+                if right == 0. {
+                    return Err(self.error("modulo by zero", &expr.pos))
+                }
+                Value::Number(left - right * (left / right).floor())
+            }
+            Expression::Max(left, right) => Value::Number(self.number(left, params)?.max(self.number(right, params)?)),
+            Expression::Min(left, right) => Value::Number(self.number(left, params)?.min(self.number(right, params)?)),
+            Expression::Join(left, right) => Value::String(self.string(left, params)? + &self.string(right, params)?),
+            Expression::Index(string, idx) => {
+                let string = self.string(string, params)?;
+                let idx = self.index(idx, string.chars().count(), params)?;
+                Value::String(string.chars().nth(idx).map(String::from).unwrap_or_default())
+            }
+            // both ends are included, an end before the start gives an empty string
+            Expression::SubString(string, start, end) => {
+                let string = self.string(string, params)?;
+                let len = string.chars().count();
+                let (start, end) = (self.index(start, len, params)?, self.index(end, len, params)?);
+                Value::String(string.chars().skip(start).take((end + 1).saturating_sub(start)).collect())
+            }
+            Expression::Contains(string, part) => Value::Bool(self.string(string, params)?.contains(&self.string(part, params)?)),
+            Expression::Call(id, args) => return self.call_function(id, args, params, &expr.pos),
+        };
+        Ok(value)
+    }
+    // orders two numbers or two strings
+    pub fn order(&mut self, left: &Located<Expression>, right: &Located<Expression>, params: &[Value]) -> Result<Ordering, Error> {
+        match (self.expression(left, params)?, self.expression(right, params)?) {
+            (Value::Number(left), Value::Number(right)) => Ok(left.partial_cmp(&right).unwrap_or(Ordering::Equal)),
+            (Value::String(left), Value::String(right)) => Ok(left.cmp(&right)),
+            (left_value, right_value) => {
+                let mut pos = left.pos.clone();
+                pos.extend(&right.pos);
+                Err(self.error(format!("can't compare {} and {}", left_value.typ(), right_value.typ()), &pos))
+            }
+        }
+    }
+    pub fn condition(&mut self, cond: &Located<Condition>, params: &[Value]) -> Result<bool, Error> {
+        Ok(match &cond.item {
+            Condition::True => true,
+            Condition::False => false,
+            Condition::And(left, right) => self.condition(left, params)? && self.condition(right, params)?,
+            Condition::Or(left, right) => self.condition(left, params)? || self.condition(right, params)?,
+            Condition::Not(cond) => !self.condition(cond, params)?,
+            Condition::EQ(left, right) => self.expression(left, params)? == self.expression(right, params)?,
+            Condition::NE(left, right) => self.expression(left, params)? != self.expression(right, params)?,
+            Condition::LT(left, right) => self.order(left, right, params)? == Ordering::Less,
+            Condition::GT(left, right) => self.order(left, right, params)? == Ordering::Greater,
+            Condition::LE(left, right) => self.order(left, right, params)? != Ordering::Greater,
+            Condition::GE(left, right) => self.order(left, right, params)? != Ordering::Less,
+            Condition::ID(_) => match self.variable(&cond.pos, params)? {
+                Value::Bool(bool) => bool,
+                value => return Err(self.error(format!("expected bool, got {}", value.typ()), &cond.pos))
+            }
+            Condition::Call(id, args) => match self.call_function(id, args, params, &cond.pos)? {
+                Value::Bool(bool) => bool,
+                value => return Err(self.error(format!("expected bool, got {}", value.typ()), &cond.pos))
+            }
+        })
+    }
+
+    // the values of the parameters, missing arguments are filled in with the defaults
+    pub fn arguments(&mut self, decl: &[Located<Param>], args: &[Argument], params: &[Value], pos: &Position) -> Result<Vec<Value>, Error> {
+        let mut values = vec![];
+        for (idx, param) in decl.iter().enumerate() {
+            // defaults can't see any parameters
+            let (arg, params) = match args.get(idx) {
+                Some(arg) => (arg, params),
+                None => match &param.item.default {
+                    Some(default) => (default, &[] as &[Value]),
+                    None => return Err(self.error(format!("missing argument for {:?}", param.item.id.item), pos))
+                }
+            };
+            values.push(self.argument(arg, params)?);
+        }
+        Ok(values)
+    }
+    pub fn argument(&mut self, arg: &Argument, params: &[Value]) -> Result<Value, Error> {
+        match arg {
+            Argument::Expression(expr) => self.expression(expr, params),
+            Argument::Condition(cond) => Ok(Value::Bool(self.condition(cond, params)?)),
+        }
+    }
+    pub fn enter(&mut self, pos: &Position) -> Result<(), Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(format!("maximum call depth of {MAX_DEPTH} exceeded"), pos))
+        }
+        self.depth += 1;
+        Ok(())
+    }
+    pub fn call_function(&mut self, id: &Located<String>, args: &[Argument], params: &[Value], pos: &Position) -> Result<Value, Error> {
+        let (actor, func) = match self.resolution.get(&id.pos) {
+            Some(Symbol::Function(actor, func)) => (actor, func),
+            // `(x)` naming a variable
+            Some(Symbol::Global(_) | Symbol::Local(_, _) | Symbol::Param(_)) => return self.variable(&id.pos, params),
+            _ => return Err(self.error(format!("unresolved function {:?}", id.item), &id.pos))
+        };
+        let function = &self.program.actors[actor].item.functions[func].item;
+        let values = self.arguments(&function.params, args, params, pos)?;
+        self.enter(pos)?;
+        let flow = self.block(&function.body, &values);
+        self.depth -= 1;
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Err(self.error(format!("function {:?} ended without returning a value", id.item), pos))
+        }
+    }
+    pub fn message(&self, id: &Located<String>) -> Result<Symbol, Error> {
+        match self.resolution.get(&id.pos) {
+            Some(symbol @ Symbol::Message(_, _)) => Ok(symbol),
+            _ => Err(self.error(format!("unresolved message {:?}", id.item), &id.pos))
+        }
+    }
+    pub fn actor(&self, id: &Located<String>) -> Result<usize, Error> {
+        match self.resolution.get(&id.pos) {
+            Some(Symbol::Actor(actor)) => Ok(actor),
+            _ => Err(self.error(format!("unresolved actor {:?}", id.item), &id.pos))
+        }
+    }
+    pub fn procedure(&self, id: &Located<String>) -> Result<&'a Procedure, Error> {
+        let Some(Symbol::Procedure(actor, proc)) = self.resolution.get(&id.pos) else {
+            return Err(self.error(format!("unresolved procedure {:?}", id.item), &id.pos))
+        };
+        Ok(&self.program.actors[actor].item.procedures[proc].item)
+    }
+    pub fn call_procedure(&mut self, id: &Located<String>, args: &[Argument], params: &[Value], pos: &Position) -> Result<(), Error> {
+        let procedure = self.procedure(id)?;
+        let values = self.arguments(&procedure.params, args, params, pos)?;
+        self.enter(pos)?;
+        let flow = self.block(&procedure.body, &values);
+        self.depth -= 1;
+        flow.map(|_| ())
+    }
+
+    pub fn statement(&mut self, stat: &Located<Statement>, params: &[Value]) -> Result<Flow, Error> {
+        match &stat.item {
+            Statement::Set(id, expr) => {
+                let value = self.expression(expr, params)?;
+                self.set(&id.pos, value)?;
+            }
+            Statement::SetCondition(id, cond) => {
+                let value = Value::Bool(self.condition(cond, params)?);
+                self.set(&id.pos, value)?;
+            }
+            Statement::If(cond, case) => if self.condition(cond, params)? {
+                return self.block(case, params)
+            }
+            Statement::IfElse(cond, case, else_case) => return if self.condition(cond, params)? {
+                self.block(case, params)
+            } else {
+                self.block(else_case, params)
+            },
+            // the count is rounded to the nearest whole number, like in Scratch
+            Statement::Repeat(count, body) => {
+                let count = self.number(count, params)?.round();
+                let mut idx = 0.;
+                while idx < count {
+                    match self.block(body, params)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Next => {}
+                        flow => return Ok(flow)
+                    }
+                    idx += 1.;
+                }
+            }
+            Statement::While(cond, body) => while self.condition(cond, params)? {
+                match self.block(body, params)? {
+                    Flow::Break => break,
+                    Flow::Normal | Flow::Next => {}
+                    flow => return Ok(flow)
+                }
+            }
+            Statement::Loop(body) => loop {
+                match self.block(body, params)? {
+                    Flow::Break => break,
+                    Flow::Normal | Flow::Next => {}
+                    flow => return Ok(flow)
+                }
+            }
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Next => return Ok(Flow::Next),
+            Statement::Return(expr) => return Ok(Flow::Return(self.expression(expr, params)?)),
+            Statement::ReturnCondition(cond) => return Ok(Flow::Return(Value::Bool(self.condition(cond, params)?))),
+            Statement::Call(id, args) => self.call_procedure(id, args, params, &stat.pos)?,
+            // only threads can be paused, functions always run to the end at once
+            Statement::Wait(_) => return Err(self.error("can't wait while a function is running", &stat.pos)),
+            Statement::Broadcast(_, _) | Statement::BroadcastWait(_, _) | Statement::Request(_, _, _, _) => return Err(self.error("can't send messages while a function is running", &stat.pos)),
+            Statement::Clone(_) | Statement::DeleteClone => return Err(self.error("can't clone while a function is running", &stat.pos)),
+            Statement::Error => {}
+        }
+        Ok(Flow::Normal)
+    }
+    pub fn block(&mut self, block: &Located<Block>, params: &[Value]) -> Result<Flow, Error> {
+        for stat in &block.item.statements {
+            match self.statement(stat, params)? {
+                Flow::Normal => {}
+                flow => return Ok(flow)
+            }
+        }
+        Ok(Flow::Normal)
+    }
+}
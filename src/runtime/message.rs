@@ -0,0 +1,52 @@
+use crate::{location::position::{Located, Position}, analysis::symbol::Symbol};
+use super::value::Value;
+
+// a message sent by a thread, delivered at the start of the next tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct Broadcast {
+    // the declaration of the message
+    pub message: Symbol,
+    // values for the parameters of the message, bound by every handler
+    pub args: Vec<Value>,
+    // id of the thread waiting for every handler to finish
+    pub sender: Option<usize>,
+    // id of the sender's request the handlers reply to
+    pub request: Option<usize>
+}
+impl Broadcast {
+    pub fn new(message: Symbol, args: Vec<Value>, sender: Option<usize>, request: Option<usize>) -> Self {
+        Self { message, args, sender, request }
+    }
+}
+
+// what a thread sent during a tick, handled at the start of the next one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mail {
+    Broadcast(Broadcast),
+    // requesting thread, request id and the value a handler returned
+    Reply(usize, usize, Value),
+    // actor to clone with the variables of the clone, copied when the clone was requested,
+    // and the position of the statement
+    Clone(usize, Vec<Value>, Position),
+    // instance of a clone that deleted itself, its threads are stopped right away
+    Delete(usize),
+}
+
+// the mail one actor sent during a tick, each tagged with the place of the sending thread in the tick
+pub type Mailbox = Vec<(usize, Mail)>;
+
+// a request a thread waits on for a reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request<'a> {
+    // ids are counted per thread
+    pub id: usize,
+    // the variable the reply is put in
+    pub var: &'a Located<String>,
+    // ticks left before the request times out
+    pub ticks: Option<usize>
+}
+impl<'a> Request<'a> {
+    pub fn new(id: usize, var: &'a Located<String>, ticks: Option<usize>) -> Self {
+        Self { id, var, ticks }
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use crate::{arguments::Arguments, location::{path::FilePath, position::Located}, parser::ast::{Program, Type}, analysis::{symbol::Resolution, checker::Slot}, error::Error};
+
+pub mod value;
+pub mod interpreter;
+pub mod thread;
+pub mod message;
+pub mod scheduler;
+pub mod pool;
+#[allow(clippy::module_inception)]
+pub mod runtime;
+
+use interpreter::Interpreter;
+use runtime::Runtime;
+
+// a setting that is a number, if given
+fn count(arguments: &Arguments, name: &str) -> Result<Option<usize>, Error> {
+    match arguments.get_setting(name) {
+        Some(count) => Ok(Some(count.parse().map_err(|_| Error::msg(format!("invalid number of {name} {count:?}")))?)),
+        None => Ok(None)
+    }
+}
+
+// runs a checked program until every thread finished
+// -ticks N: stops after N ticks
+// -threads N: runs on N OS threads
+// -clones N: allows N live clones at once
+pub fn run<'a>(path: &FilePath, program: &'a Located<Program>, resolution: &'a Resolution, types: &HashMap<Slot, Type>, arguments: &Arguments) -> Result<Runtime<'a>, Vec<Error>> {
+    let ticks = count(arguments, "ticks")?;
+    let scheduler = scheduler::from_arguments(&program.item, arguments)?;
+    let mut runtime = Runtime::new(Interpreter::new(path.clone(), &program.item, resolution, types), scheduler);
+    if let Some(threads) = count(arguments, "threads")? {
+        runtime = runtime.threaded(threads);
+    }
+    if let Some(clones) = count(arguments, "clones")? {
+        runtime = runtime.max_clones(clones);
+    }
+    runtime.run(ticks)?;
+    Ok(runtime)
+}
+
+#[cfg(test)]
+mod tests;
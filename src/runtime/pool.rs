@@ -0,0 +1,100 @@
+use std::{thread::{self, Scope}, sync::mpsc::{self, Sender, Receiver}, panic::{self, AssertUnwindSafe}};
+use crate::error::Error;
+use super::{interpreter::{Interpreter, STACK_SIZE}, thread::Thread, message::Mailbox, runtime::Runtime};
+
+// the threads of some actors and their variables, run for one tick by a worker
+pub struct Job<'a> {
+    pub interpreter: Interpreter<'a>,
+    pub threads: Vec<Thread<'a>>,
+    // place in the whole tick and id of every thread to run
+    pub order: Vec<(usize, usize)>
+}
+impl<'a> Job<'a> {
+    pub fn new(interpreter: Interpreter<'a>, threads: Vec<Thread<'a>>, order: Vec<(usize, usize)>) -> Self {
+        Self { interpreter, threads, order }
+    }
+    // runs the threads until one fails, keeping what is needed to take the job back to an earlier place
+    pub fn run(mut self) -> Done<'a> {
+        self.interpreter.journal = Some(vec![]);
+        let mut mailboxes = vec![vec![]; self.interpreter.program.actors.len()];
+        let mut steps = vec![];
+        let result = Runtime::tick(&mut self.interpreter, &mut self.threads, &self.order, &mut mailboxes, Some(&mut steps));
+        Done { job: self, steps, mailboxes, result }
+    }
+}
+
+// what a thread was like before its turn in the tick, with the number of variable writes before it
+pub struct Step<'a> {
+    pub place: usize,
+    pub writes: usize,
+    // the thread itself and every other thread its turn removed
+    pub threads: Vec<Thread<'a>>
+}
+
+// a job after a worker ran it
+pub struct Done<'a> {
+    pub job: Job<'a>,
+    // every turn the threads took, in the order they took it
+    pub steps: Vec<Step<'a>>,
+    // mail sent by every actor, tagged with the place of the sending thread
+    pub mailboxes: Vec<Mailbox>,
+    // the error of the failed thread, with its place
+    pub result: Result<(), (usize, Error)>
+}
+impl<'a> Done<'a> {
+    // the job and its mail as if the tick stopped before `place`, like it does on a single thread when the thread at `place` fails,
+    // the turns from `place` on are undone from the last one back
+    pub fn stop_at(self, place: usize) -> (Job<'a>, Vec<Mailbox>) {
+        let Done { mut job, mut steps, mut mailboxes, result: _ } = self;
+        while let Some(step) = steps.pop_if(|step| step.place >= place) {
+            job.interpreter.undo(step.writes);
+            for thread in step.threads {
+                match job.threads.iter_mut().find(|other| other.id == thread.id) {
+                    Some(other) => *other = thread,
+                    None => job.threads.push(thread)
+                }
+            }
+        }
+        for mailbox in &mut mailboxes {
+            mailbox.retain(|&(sent, _)| sent < place);
+        }
+        (job, mailboxes)
+    }
+}
+
+// OS threads that stay alive for a whole run, each one runs the jobs sent through its own channel
+pub struct Pool<'a> {
+    jobs: Vec<Sender<Job<'a>>>,
+    // finished jobs with the index of their worker, `None` if the worker panicked
+    done: Receiver<(usize, Option<Done<'a>>)>
+}
+impl<'a> Pool<'a> {
+    pub fn new<'scope>(scope: &'scope Scope<'scope, '_>, workers: usize) -> Result<Self, Error> where 'a: 'scope {
+        let (done_sender, done) = mpsc::channel();
+        let mut jobs = vec![];
+        for worker in 0..workers {
+            let (sender, receiver) = mpsc::channel::<Job<'a>>();
+            let done = done_sender.clone();
+            thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, move || {
+                for job in receiver {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| job.run())).ok();
+                    if done.send((worker, result)).is_err() { break; }
+                }
+            }).map_err(|err| Error::msg(format!("couldn't start a worker thread: {err}")))?;
+            jobs.push(sender);
+        }
+        Ok(Self { jobs, done })
+    }
+    // runs one job on every worker, there have to be as many jobs as workers
+    pub fn run(&self, jobs: Vec<Job<'a>>) -> Result<Vec<Done<'a>>, Error> {
+        for (sender, job) in self.jobs.iter().zip(jobs) {
+            sender.send(job).map_err(|_| Error::msg("a worker thread stopped"))?;
+        }
+        let mut done = self.jobs.iter().map(|_| None).collect::<Vec<Option<Done<'a>>>>();
+        for _ in 0..self.jobs.len() {
+            let (worker, result) = self.done.recv().map_err(|_| Error::msg("a worker thread stopped"))?;
+            done[worker] = Some(result.ok_or_else(|| Error::msg("a worker thread panicked"))?);
+        }
+        Ok(done.into_iter().flatten().collect())
+    }
+}
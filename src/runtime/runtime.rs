@@ -0,0 +1,323 @@
+use std::{collections::HashMap, thread::Scope};
+use crate::{location::position::{Located, Position}, error::Error, parser::ast::*};
+use super::{value::Value, interpreter::Interpreter, thread::{Thread, Status}, message::{Broadcast, Mail, Mailbox}, scheduler::Scheduler, pool::{Pool, Job, Step}};
+
+// live clones allowed when no other maximum is given, the same as in Scratch
+pub const MAX_CLONES: usize = 300;
+
+// runs the events of every actor as green threads in deterministic ticks,
+// each tick every thread runs once until it yields
+// messages and replies sent during a tick only arrive at the start of the next one,
+// so threads of actors that don't share variables can run a tick on separate OS threads
+pub struct Runtime<'a> {
+    pub interpreter: Interpreter<'a>,
+    // decides the order the threads run in each tick
+    scheduler: Box<dyn Scheduler>,
+    pub threads: Vec<Thread<'a>>,
+    // id of the next thread to be started
+    next_id: usize,
+    // mail every actor sent during the last tick, tagged with the place of the sending thread in the tick
+    pub mailboxes: Vec<Mailbox>,
+    // actors run by each worker thread, empty when running on the current thread only
+    workers: Vec<Vec<usize>>,
+    // live clones allowed at once
+    max_clones: usize,
+    // whether the condition of each condition event held in the last tick, by instance and event
+    conditions: HashMap<(usize, usize), bool>,
+    // number of ticks run so far
+    pub tick: usize
+}
+impl<'a> Runtime<'a> {
+    pub fn new(interpreter: Interpreter<'a>, scheduler: Box<dyn Scheduler>) -> Self {
+        let mailboxes = vec![vec![]; interpreter.program.actors.len()];
+        Self { interpreter, scheduler, threads: vec![], next_id: 0, mailboxes, workers: vec![], max_clones: MAX_CLONES, conditions: HashMap::new(), tick: 0 }
+    }
+    pub fn max_clones(mut self, max_clones: usize) -> Self {
+        self.max_clones = max_clones;
+        self
+    }
+    // runs the threads on up to `workers` OS threads, every actor using global variables on the same one,
+    // so they still run one after another in the scheduler's order,
+    // and an actor cloning another one next to it, so it can copy the other actor's variables
+    pub fn threaded(mut self, workers: usize) -> Self {
+        let workers = workers.max(1);
+        let actors = self.interpreter.program.actors.len();
+        let (shared, isolated) = (0..actors).partition::<Vec<usize>, _>(|&actor| self.interpreter.resolution.uses_globals(actor));
+        let mut groups = std::iter::once(shared).chain(isolated.into_iter().map(|actor| vec![actor])).filter(|group| !group.is_empty()).collect::<Vec<Vec<usize>>>();
+        for &(actor, cloned) in self.interpreter.resolution.copies() {
+            let (Some(first), Some(second)) = (groups.iter().position(|group| group.contains(&actor)), groups.iter().position(|group| group.contains(&cloned))) else { continue };
+            if first != second {
+                let group = groups.remove(first.max(second));
+                groups[first.min(second)].extend(group);
+            }
+        }
+        let mut assigned = vec![vec![]; workers];
+        for (idx, group) in groups.into_iter().enumerate() {
+            assigned[idx % workers].extend(group);
+        }
+        self.workers = assigned.into_iter().filter(|actors| !actors.is_empty()).collect();
+        self
+    }
+    pub fn running(&self, event: (usize, usize), instance: usize) -> bool {
+        self.threads.iter().any(|thread| thread.event == event && thread.instance == instance)
+    }
+    // every instance that wasn't deleted with its actor, the actors themselves first
+    pub fn instances(&self) -> Vec<(usize, usize)> {
+        self.interpreter.instances.iter().enumerate().filter_map(|(instance, actor)| Some((instance, (*actor)?))).collect()
+    }
+    // starts a new thread after every other one and returns its id
+    pub fn spawn(&mut self, event: (usize, usize), instance: usize, body: &'a Located<Block>, bindings: Vec<Value>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.threads.push(Thread::new(id, event, instance, body, bindings));
+        id
+    }
+    // handles the mail of the last tick in the order it was sent,
+    // then lets threads continue whose handlers all finished
+    pub fn deliver(&mut self) -> Result<(), Error> {
+        let mut mail = self.mailboxes.iter_mut().flat_map(|mailbox| mailbox.drain(..)).collect::<Vec<(usize, Mail)>>();
+        mail.sort_by_key(|(place, _)| *place);
+        let mail = mail.into_iter().map(|(_, mail)| mail).collect::<Vec<Mail>>();
+        // the threads of deleted clones stopped during the last tick already, so they don't get any other mail
+        for mail in &mail {
+            if let &Mail::Delete(instance) = mail {
+                self.interpreter.delete(instance);
+                self.conditions.retain(|&(other, _), _| other != instance);
+            }
+        }
+        for mail in mail {
+            match mail {
+                Mail::Broadcast(broadcast) => self.broadcast(broadcast),
+                Mail::Reply(requester, request, value) => self.reply((requester, request), value)?,
+                Mail::Clone(actor, locals, pos) => self.clone_actor(actor, locals, &pos)?,
+                Mail::Delete(_) => {}
+            }
+        }
+        let ids = self.threads.iter().map(|thread| thread.id).collect::<Vec<usize>>();
+        for thread in &mut self.threads {
+            let Some(handlers) = &thread.awaiting else { continue };
+            if !handlers.iter().any(|handler| ids.contains(handler)) {
+                // either every handler finished without replying or there were none
+                thread.awaiting = None;
+                thread.request = None;
+            }
+        }
+        Ok(())
+    }
+    // starts every event triggered by a message, a handler of a broadcast that is still running starts over in its place
+    pub fn broadcast(&mut self, Broadcast { message, args, sender, request }: Broadcast) {
+        let program = self.interpreter.program;
+        let mut handlers = vec![];
+        for (instance, actor) in self.instances() {
+            for (event, Located { item: Event { trigger, body }, pos: _ }) in program.actors[actor].item.events.iter().enumerate() {
+                let Trigger::Message(id, _) = &trigger.item else { continue };
+                if self.interpreter.message(id).ok() != Some(message) { continue }
+                // every request gets handlers of its own, so concurrent requests don't restart each other
+                let running = self.threads.iter().position(|thread| thread.event == (actor, event) && thread.instance == instance && thread.reply_to.is_none());
+                let idx = match running.filter(|_| request.is_none()) {
+                    Some(idx) => {
+                        self.threads[idx].restart(body, args.clone());
+                        idx
+                    }
+                    None => {
+                        self.spawn((actor, event), instance, body, args.clone());
+                        self.threads.len() - 1
+                    }
+                };
+                let thread = &mut self.threads[idx];
+                // replies only go back to the thread that sent the request
+                thread.reply_to = sender.zip(request);
+                handlers.push(thread.id);
+            }
+        }
+        // a sender that was restarted by its own message doesn't wait anymore
+        let Some(sender) = sender.filter(|sender| !handlers.contains(sender)) else { return };
+        if let Some(thread) = self.threads.iter_mut().find(|thread| thread.id == sender) {
+            thread.awaiting = Some(handlers);
+        }
+    }
+    // puts the first reply to a request into its variable and lets the requester continue
+    pub fn reply(&mut self, (requester, request): (usize, usize), value: Value) -> Result<(), Error> {
+        let Some(thread) = self.threads.iter_mut().find(|thread| thread.id == requester) else { return Ok(()) };
+        let Some(var) = thread.request.as_ref().filter(|pending| pending.id == request).map(|pending| pending.var) else { return Ok(()) };
+        thread.request = None;
+        thread.awaiting = None;
+        self.interpreter.instance = thread.instance;
+        self.interpreter.set(&var.pos, value)
+    }
+    // adds a clone of the actor and starts its clone events
+    pub fn clone_actor(&mut self, actor: usize, locals: Vec<Value>, pos: &Position) -> Result<(), Error> {
+        if self.interpreter.clones() >= self.max_clones {
+            return Err(self.interpreter.error(format!("maximum of {} clones exceeded", self.max_clones), pos))
+        }
+        let instance = self.interpreter.clone_actor(actor, locals);
+        let program = self.interpreter.program;
+        for (idx, Located { item: Event { trigger, body }, pos: _ }) in program.actors[actor].item.events.iter().enumerate() {
+            if let Trigger::Clone = trigger.item {
+                self.spawn((actor, idx), instance, body, vec![]);
+            }
+        }
+        Ok(())
+    }
+    // initializes the variables and starts every start event
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.interpreter.init()?;
+        let program = self.interpreter.program;
+        for (actor, Located { item: Actor { events, .. }, pos: _ }) in program.actors.iter().enumerate() {
+            for (idx, Located { item: Event { trigger, body }, pos: _ }) in events.iter().enumerate() {
+                if let Trigger::Start = trigger.item {
+                    self.spawn((actor, idx), actor, body, vec![]);
+                }
+            }
+        }
+        Ok(())
+    }
+    // condition events start when their condition becomes true and they aren't running already,
+    // for every clone on its own
+    pub fn triggers(&mut self) -> Result<(), Error> {
+        let program = self.interpreter.program;
+        for (instance, actor) in self.instances() {
+            self.interpreter.instance = instance;
+            for (idx, Located { item: Event { trigger, body }, pos: _ }) in program.actors[actor].item.events.iter().enumerate() {
+                let Trigger::Condition(cond) = &trigger.item else { continue };
+                let holds = self.interpreter.condition(cond, &[])?;
+                let held = self.conditions.insert((instance, idx), holds).unwrap_or(false);
+                if holds && !held && !self.running((actor, idx), instance) {
+                    self.spawn((actor, idx), instance, body, vec![]);
+                }
+            }
+        }
+        Ok(())
+    }
+    // worker threads for `step_on` that live as long as the scope, `None` if the runtime isn't threaded
+    pub fn pool<'scope>(&self, scope: &'scope Scope<'scope, '_>) -> Result<Option<Pool<'a>>, Error> where 'a: 'scope {
+        if self.workers.is_empty() {
+            return Ok(None)
+        }
+        Pool::new(scope, self.workers.len()).map(Some)
+    }
+    // advances every thread by one tick in the order the scheduler picks, returns whether any thread or mail is left
+    // a threaded runtime starts and stops its worker threads on every call, a host stepping it more than once
+    // should keep them with `pool` and call `step_on` instead, like `run` does
+    pub fn step(&mut self) -> Result<bool, Error> {
+        std::thread::scope(|scope| {
+            let pool = self.pool(scope)?;
+            self.step_on(pool.as_ref())
+        })
+    }
+    // a step on the workers of the pool if there is one, on the current thread otherwise
+    pub fn step_on(&mut self, pool: Option<&Pool<'a>>) -> Result<bool, Error> {
+        self.deliver()?;
+        self.triggers()?;
+        let order = self.scheduler.order(&self.threads).into_iter().enumerate().collect::<Vec<(usize, usize)>>();
+        match pool {
+            Some(pool) => self.parallel(pool, &order)?,
+            None => Self::tick(&mut self.interpreter, &mut self.threads, &order, &mut self.mailboxes, None).map_err(|(_, err)| err)?
+        }
+        self.tick += 1;
+        Ok(!self.threads.is_empty() || self.mailboxes.iter().any(|mailbox| !mailbox.is_empty()))
+    }
+    // runs the threads in `order`, given as their place in the whole tick and their id,
+    // the mail they send goes to the mailbox of their actor, tagged with the place of the sender
+    // and a failed thread stops the tick, its error comes with its place
+    // with `steps` every thread is kept as it was before its turn, so the turn can be undone
+    pub fn tick(interpreter: &mut Interpreter<'a>, threads: &mut Vec<Thread<'a>>, order: &[(usize, usize)], mailboxes: &mut [Mailbox], mut steps: Option<&mut Vec<Step<'a>>>) -> Result<(), (usize, Error)> {
+        for &(place, id) in order {
+            let Some(idx) = threads.iter().position(|thread| thread.id == id) else { continue };
+            if let Some(steps) = steps.as_deref_mut() {
+                let writes = interpreter.journal.as_ref().map_or(0, Vec::len);
+                steps.push(Step { place, writes, threads: vec![threads[idx].clone()] });
+            }
+            let thread = &mut threads[idx];
+            if thread.awaiting.is_some() {
+                match thread.request.as_mut().and_then(|request| request.ticks.as_mut()) {
+                    // a request that timed out leaves its variable as it is
+                    Some(0) => {
+                        thread.awaiting = None;
+                        thread.request = None;
+                    }
+                    Some(ticks) => {
+                        *ticks -= 1;
+                        continue;
+                    }
+                    None => continue
+                }
+            }
+            if thread.sleep > 0 {
+                thread.sleep -= 1;
+                continue;
+            }
+            let status = thread.step(interpreter).map_err(|err| (place, err))?;
+            let mailbox = &mut mailboxes[thread.event.0];
+            mailbox.extend(thread.outbox.drain(..).map(|sent| (place, sent)));
+            match status {
+                Status::Yielded => {}
+                Status::Finished => {
+                    threads.remove(idx);
+                }
+                Status::Returned(value) => {
+                    let thread = threads.remove(idx);
+                    if let Some((requester, request)) = thread.reply_to {
+                        mailbox.push((place, Mail::Reply(requester, request, value)));
+                    }
+                }
+                // every thread of a clone runs on the same OS thread, so they can all be stopped here
+                Status::Deleted => {
+                    let instance = threads[idx].instance;
+                    let (deleted, kept) = std::mem::take(threads).into_iter().partition::<Vec<Thread>, _>(|thread| thread.instance == instance);
+                    *threads = kept;
+                    // the thread that deleted the clone is kept already
+                    if let Some(step) = steps.as_deref_mut().and_then(|steps| steps.last_mut()) {
+                        step.threads.extend(deleted.into_iter().filter(|thread| thread.id != id));
+                    }
+                    mailbox.push((place, Mail::Delete(instance)));
+                }
+            }
+        }
+        Ok(())
+    }
+    // runs a tick with the threads of each worker on its OS thread,
+    // afterwards threads, variables and mail are the same as if the tick ran on one thread:
+    // when a thread fails, the other workers are taken back to the place of the first failed thread
+    fn parallel(&mut self, pool: &Pool<'a>, order: &[(usize, usize)]) -> Result<(), Error> {
+        let mut threads = std::mem::take(&mut self.threads);
+        let mut jobs = vec![];
+        for actors in &self.workers {
+            let (own, rest) = threads.into_iter().partition::<Vec<Thread>, _>(|thread| actors.contains(&thread.event.0));
+            threads = rest;
+            let order = order.iter().copied().filter(|(_, id)| own.iter().any(|thread| &thread.id == id)).collect::<Vec<(usize, usize)>>();
+            jobs.push(Job::new(self.interpreter.split(actors), own, order));
+        }
+        self.threads = threads;
+        let done = pool.run(jobs)?;
+        let failed = done.iter().filter_map(|done| done.result.as_ref().err().map(|&(place, _)| place)).min();
+        let mut error = None;
+        for done in done {
+            let place = done.result.as_ref().err().map(|&(place, _)| place);
+            let (job, mailboxes) = match failed {
+                Some(failed) if place != Some(failed) => done.stop_at(failed),
+                _ => {
+                    error = error.or(done.result.err().map(|(_, err)| err));
+                    (done.job, done.mailboxes)
+                }
+            };
+            self.interpreter.join(job.interpreter);
+            self.threads.extend(job.threads);
+            for (mailbox, sent) in self.mailboxes.iter_mut().zip(mailboxes) {
+                mailbox.extend(sent);
+            }
+        }
+        // new threads are always started last, so ids are in the order threads were started
+        self.threads.sort_by_key(|thread| thread.id);
+        error.map_or(Ok(()), Err)
+    }
+    // runs until every thread finished or the maximum number of ticks is reached
+    pub fn run(&mut self, ticks: Option<usize>) -> Result<(), Error> {
+        self.start()?;
+        std::thread::scope(|scope| {
+            let pool = self.pool(scope)?;
+            while ticks.is_none_or(|ticks| self.tick < ticks) && self.step_on(pool.as_ref())? {}
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use std::cmp::Reverse;
+use crate::{arguments::Arguments, error::Error, parser::ast::Program};
+use super::thread::Thread;
+
+// decides the order threads run in during a tick
+pub trait Scheduler {
+    // ids of the threads, in the order they run this tick
+    fn order(&mut self, threads: &[Thread]) -> Vec<usize>;
+}
+
+// every thread in the order it was started
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin;
+impl Scheduler for RoundRobin {
+    fn order(&mut self, threads: &[Thread]) -> Vec<usize> {
+        threads.iter().map(|thread| thread.id).collect()
+    }
+}
+
+// threads of actors with a higher priority first, equal ones in the order they were started
+#[derive(Debug, Clone, Default)]
+pub struct Priority {
+    // indexed by actor
+    priorities: Vec<i64>
+}
+impl Priority {
+    pub fn new(priorities: Vec<i64>) -> Self {
+        Self { priorities }
+    }
+    pub fn priority(&self, actor: usize) -> i64 {
+        self.priorities.get(actor).copied().unwrap_or_default()
+    }
+}
+impl Scheduler for Priority {
+    fn order(&mut self, threads: &[Thread]) -> Vec<usize> {
+        let mut threads = threads.iter().collect::<Vec<&Thread>>();
+        threads.sort_by_key(|thread| Reverse(self.priority(thread.event.0)));
+        threads.iter().map(|thread| thread.id).collect()
+    }
+}
+
+// a shuffled order every tick, the same seed always gives the same orders
+#[derive(Debug, Clone)]
+pub struct Random {
+    rng: Rng
+}
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Rng::new(seed) }
+    }
+}
+impl Scheduler for Random {
+    fn order(&mut self, threads: &[Thread]) -> Vec<usize> {
+        let mut order = threads.iter().map(|thread| thread.id).collect::<Vec<usize>>();
+        for idx in (1..order.len()).rev() {
+            order.swap(idx, self.rng.below(idx + 1));
+        }
+        order
+    }
+}
+
+// xorshift64*, small and the same on every platform
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // the state must never be 0
+        Self { state: (seed ^ 0x9E37_79B9_7F4A_7C15).max(1) }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    // a number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// -scheduler round-robin|priority|random
+// -priority NAME=PRIORITY,...
+// -seed SEED
+pub fn from_arguments(program: &Program, arguments: &Arguments) -> Result<Box<dyn Scheduler>, Error> {
+    match arguments.get_setting("scheduler").map(String::as_str) {
+        None | Some("round-robin") => Ok(Box::new(RoundRobin)),
+        Some("priority") => {
+            let mut priorities = vec![0; program.actors.len()];
+            let entries = arguments.get_setting("priority").map(|entries| entries.split(',').collect::<Vec<&str>>()).unwrap_or_default();
+            for entry in entries.into_iter().filter(|entry| !entry.is_empty()) {
+                let Some((name, priority)) = entry.split_once('=') else {
+                    return Err(Error::msg(format!("expected NAME=PRIORITY, got {entry:?}")))
+                };
+                let Some(actor) = program.actors.iter().position(|actor| actor.item.id.item == name) else {
+                    return Err(Error::msg(format!("unknown actor {name:?}")))
+                };
+                priorities[actor] = priority.parse().map_err(|_| Error::msg(format!("invalid priority {priority:?}")))?;
+            }
+            Ok(Box::new(Priority::new(priorities)))
+        }
+        Some("random") => {
+            let seed = match arguments.get_setting("seed") {
+                Some(seed) => seed.parse().map_err(|_| Error::msg(format!("invalid seed {seed:?}")))?,
+                None => 0
+            };
+            Ok(Box::new(Random::new(seed)))
+        }
+        Some(name) => Err(Error::msg(format!("unknown scheduler {name:?}, expected one of \"round-robin\", \"priority\", \"random\"")))
+    }
+}
@@ -0,0 +1,467 @@
+use crate::{location::path::FilePath, error::Error, lexer, parser, analysis, testing::located};
+use super::{interpreter::{Interpreter, MAX_DEPTH, STACK_SIZE}, runtime::Runtime, scheduler::{Scheduler, RoundRobin, Priority, Random}};
+
+// runs a program for at most `ticks` ticks and returns the final value of a variable
+fn run(text: &str, ticks: usize, var: &str) -> String {
+    run_with(text, ticks, var, Box::new(RoundRobin))
+}
+fn run_with(text: &str, ticks: usize, var: &str, scheduler: Box<dyn Scheduler>) -> String {
+    let (_, value) = variables(text, ticks, scheduler, None).into_iter().find(|(name, _)| name == var).unwrap();
+    value
+}
+// every variable after the run, on `threads` OS threads if given
+fn variables(text: &str, ticks: usize, scheduler: Box<dyn Scheduler>, threads: Option<usize>) -> Vec<(String, String)> {
+    let (result, variables) = outcome(text, ticks, scheduler, threads);
+    result.unwrap();
+    variables
+}
+// a runtime for the program, which lives as long as the test so that it can hold on to the program
+fn compile(text: &str, scheduler: Box<dyn Scheduler>) -> Result<Runtime<'static>, Vec<Error>> {
+    let path = FilePath::Input(text.to_string());
+    let program = Box::leak(Box::new(parser::parse(&path, lexer::lex(&path, text.to_string())?)?));
+    let resolution = Box::leak(Box::new(analysis::resolve(&path, program)?));
+    let types = Box::leak(Box::new(analysis::check(&path, program, resolution)?));
+    Ok(Runtime::new(Interpreter::new(path, &program.item, resolution, types), scheduler))
+}
+// the error the run stopped with, if any, and every variable after it
+fn outcome(text: &str, ticks: usize, scheduler: Box<dyn Scheduler>, threads: Option<usize>) -> (Result<(), String>, Vec<(String, String)>) {
+    let mut runtime = compile(text, scheduler).unwrap();
+    if let Some(threads) = threads {
+        runtime = runtime.threaded(threads);
+    }
+    let result = runtime.run(Some(ticks)).map_err(|error| located(&error));
+    (result, runtime.interpreter.variables().into_iter().map(|(name, value)| (name, value.to_string())).collect())
+}
+// the error a program stops with
+fn error(text: &str, ticks: usize) -> String {
+    located(&compile(text, Box::new(RoundRobin)).unwrap().run(Some(ticks)).unwrap_err())
+}
+// runs a program cooperatively and on 1 to 4 OS threads, which must all end with the same variables
+fn assert_same_in_every_mode(text: &str, ticks: usize) {
+    let cooperative = variables(text, ticks, Box::new(RoundRobin), None);
+    for threads in 1..=4 {
+        assert_eq!(variables(text, ticks, Box::new(RoundRobin), Some(threads)), cooperative, "{threads} threads");
+    }
+    for seed in 0..4 {
+        let cooperative = variables(text, ticks, Box::new(Random::new(seed)), None);
+        assert_eq!(variables(text, ticks, Box::new(Random::new(seed)), Some(3)), cooperative, "seed {seed}");
+    }
+}
+
+#[test]
+fn broadcast_is_delivered_next_tick() {
+    let text = r#"
+        data { (log = "") }
+        messages { (go) }
+        actor A {
+            events {
+                |start| { [broadcast go] [set log (join log "s")] [wait 0] [set log (join log "t")] }
+                |go| { [set log (join log "h")] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 10, "log"), "sth");
+}
+
+#[test]
+fn handlers_start_in_declaration_order() {
+    let text = r#"
+        data { (log = "") }
+        messages { (go) }
+        actor A { events { |go| { [set log (join log "a1")] } |go| { [set log (join log "a2")] } } }
+        actor B { events { |start| { [broadcast go] } |go| { [set log (join log "b")] } } }
+    "#;
+    assert_eq!(run(text, 10, "log"), "a1a2b");
+}
+
+#[test]
+fn local_messages_reach_other_actors() {
+    let text = r#"
+        data { (log = "") }
+        actor A { messages { (ping n) } events { |ping n| { [set log (join log n)] } } }
+        actor B { events { |start| { [broadcast ping "a"] } |ping n| { [set log (join log "b")] } } }
+    "#;
+    assert_eq!(run(text, 10, "log"), "ab");
+}
+
+#[test]
+fn broadcasts_are_delivered_in_send_order() {
+    let text = r#"
+        data { (log = "") }
+        messages { (one) (two) }
+        actor A {
+            events {
+                |start| { [broadcast two] [broadcast one] }
+                |one| { [set log (join log "1")] }
+                |two| { [set log (join log "2")] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 10, "log"), "21");
+}
+
+#[test]
+fn running_handler_restarts() {
+    let text = r#"
+        data { (log = "") }
+        messages { (go) }
+        actor A {
+            events {
+                |start| { [broadcast go] [wait 1] [broadcast go] }
+                |go| { [set log (join log "<")] [wait 3] [set log (join log ">")] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 20, "log"), "<<>");
+}
+
+#[test]
+fn broadcast_wait_resumes_after_every_handler() {
+    let text = r#"
+        data { (log = "") }
+        messages { (go) }
+        actor A {
+            events {
+                |start| { [broadcast_wait go] [set log (join log "done")] }
+                |go| { [wait 3] [set log (join log "slow")] }
+            }
+        }
+        actor B { events { |go| { [set log (join log "fast")] } } }
+    "#;
+    assert_eq!(run(text, 20, "log"), "fastslowdone");
+}
+
+#[test]
+fn broadcast_wait_without_handlers_resumes_next_tick() {
+    let text = r#"
+        data { (log = "") }
+        messages { (go) }
+        actor A {
+            events {
+                |start| { [broadcast_wait go] [set log (join log "a")] }
+                |start| { [set log (join log "b")] [wait 0] [set log (join log "c")] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 10, "log"), "bac");
+}
+
+#[test]
+fn handlers_bind_broadcast_arguments() {
+    let text = r#"
+        data { (log = "") (total = 0) }
+        messages { (hit amount: number direction) }
+        actor A {
+            events {
+                |start| { [broadcast_wait hit 2 "left"] [broadcast hit 3 "right"] }
+                |hit a d| { [set total (add total a)] [set log (join log d)] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 10, "total"), "5");
+    assert_eq!(run(text, 10, "log"), "leftright");
+}
+
+#[test]
+fn replies_go_to_the_requester_only() {
+    let text = r#"
+        data { (a = 0) (b = 0) (log = "") }
+        messages { (square n: number) }
+        actor Server { events { |square n| { [wait 1] [return (mul n n)] } } }
+        actor A { events { |start| { [request a square 3] [set log (join log "a")] } } }
+        actor B { events { |start| { [request b square 4] [set log (join log "b")] } } }
+    "#;
+    assert_eq!(run(text, 10, "a"), "9");
+    assert_eq!(run(text, 10, "b"), "16");
+    assert_eq!(run(text, 10, "log"), "ab");
+}
+
+#[test]
+fn request_times_out() {
+    let text = r#"
+        data { (answer = 1) (log = "") }
+        messages { (slow) }
+        actor Server { events { |slow| { [wait 5] [set log (join log "late")] [return 2] } } }
+        actor A { events { |start| { [request answer slow timeout 2] [set log (join log "timeout")] } } }
+    "#;
+    assert_eq!(run(text, 20, "answer"), "1");
+    assert_eq!(run(text, 20, "log"), "timeoutlate");
+}
+
+const INTERLEAVED: &str = r#"
+    data { (log = "") }
+    messages { (go) }
+    actor A { events { |start| { [repeat 4 { [set log (join log "a")] }] [broadcast go] } } }
+    actor B { events { |start| { [repeat 4 { [set log (join log "b")] }] } |go| { [set log (join log "!")] } } }
+    actor C { events { |start| { [repeat 4 { [set log (join log "c")] }] } } }
+"#;
+
+#[test]
+fn priority_runs_higher_actors_first() {
+    assert_eq!(run_with(INTERLEAVED, 20, "log", Box::new(Priority::new(vec![0, 1, 2]))), "cbacbacbacba!");
+    // the whole range of priorities can be used
+    assert_eq!(run_with(INTERLEAVED, 20, "log", Box::new(Priority::new(vec![i64::MIN, i64::MAX, 0]))), "bcabcabcabca!");
+    assert_eq!(run_with(INTERLEAVED, 20, "log", Box::new(Priority::new(vec![i64::MAX, i64::MIN, i64::MIN]))), "abcabcabcabc!");
+}
+
+#[test]
+fn random_order_depends_only_on_the_seed() {
+    let orders = (0..8).map(|seed| run_with(INTERLEAVED, 20, "log", Box::new(Random::new(seed)))).collect::<Vec<String>>();
+    for (seed, order) in orders.iter().enumerate() {
+        assert_eq!(&run_with(INTERLEAVED, 20, "log", Box::new(Random::new(seed as u64))), order);
+    }
+    assert!(orders.iter().any(|order| order != &orders[0]));
+}
+
+#[test]
+fn isolated_actors_run_the_same_threaded() {
+    let text = r#"
+        messages { (tick n: number) (total n: number) (triple n: number) }
+        actor Counter {
+            data { (count = 0) (sum = 0) }
+            events {
+                |start| { [repeat 50 { [set count (add count 1)] [broadcast tick count] }] }
+                |total n| { [set sum (add sum n)] }
+            }
+        }
+        actor Squares {
+            data { (last = 0) (seen = 0) }
+            events {
+                |tick n| { [set last (mul n n)] [set seen (add seen n)] [broadcast total last] }
+            }
+        }
+        actor Server {
+            data { (served = 0) }
+            functions { #(triple) (n) (mul n 3) }
+            events { |triple n| { [wait (mod n 3)] [set served (add served 1)] [return (triple n)] } }
+        }
+        actor Client {
+            data { (answers = 0) (x = 0) (n = 0) }
+            events { |start| { [repeat 5 { [set n (add n 1)] [request x triple n] [set answers (add (mul answers 100) x)] }] } }
+        }
+    "#;
+    assert_same_in_every_mode(text, 200);
+}
+
+#[test]
+fn actors_sharing_globals_run_the_same_threaded() {
+    assert_same_in_every_mode(INTERLEAVED, 20);
+    let text = r#"
+        data { (log = "") (shared = 0) }
+        messages { (go) }
+        actor A { events { |start| { [repeat 20 { [set shared (add shared 1)] [set log (join log "a")] }] [broadcast_wait go] [set log (join log "!")] } } }
+        actor B { events { |start| { [repeat 20 { [set shared (mul shared 2)] [set log (join log "b")] }] } } }
+        actor C { data { (mine = 0) } events { |go| { [wait 2] [set mine 7] } |<gt mine 5>| { [set mine 0] } } }
+        actor D { data { (steps = 0) } events { |start| { [loop { [set steps (add steps 1)] }] } } }
+    "#;
+    assert_same_in_every_mode(text, 60);
+}
+
+#[test]
+fn threaded_runs_stop_like_cooperative_ones() {
+    let text = r#"
+        data { (g = 0) }
+        messages { (hit) }
+        actor A { data { (n = 0) } events { |start| { [repeat 10 { [set n (add n 1)] }] } } }
+        actor B { data { (n = 0) } events { |start| { [repeat 3 { [set n (add n 1)] [broadcast hit] }] [set n (div n 0)] } } }
+        actor C { data { (n = 0) } events { |start| { [repeat 10 { [set n (add n 1)] }] } |hit| { [set n (add n 100)] } } }
+        actor D { events { |start| { [repeat 10 { [set g (add g 1)] }] } } }
+        actor E { data { (n = 0) } events { |start| { [repeat 3 { [set n (add n 1)] }] [set n (mod n 0)] } } }
+    "#;
+    // B fails in the fourth tick, the actors after it don't run that tick
+    let (result, variables) = outcome(text, 20, Box::new(RoundRobin), None);
+    assert_eq!(result, Err("5:111: division by zero".to_string()));
+    assert_eq!(variables, [("g", "3"), ("A.n", "4"), ("B.n", "3"), ("C.n", "203"), ("E.n", "3")].map(|(name, value)| (name.to_string(), value.to_string())));
+    for threads in 1..=4 {
+        assert_eq!(outcome(text, 20, Box::new(RoundRobin), Some(threads)), (result.clone(), variables.clone()), "{threads} threads");
+    }
+    // another order lets E fail first
+    for seed in 0..8 {
+        let cooperative = outcome(text, 20, Box::new(Random::new(seed)), None);
+        assert_eq!(outcome(text, 20, Box::new(Random::new(seed)), Some(3)), cooperative, "seed {seed}");
+    }
+}
+
+#[test]
+fn threaded_steps_keep_their_workers() {
+    let text = r#"
+        data { (g = 0) }
+        messages { (hit) (ask n: number) }
+        actor A { data { (n = 0) } events { |start| { [repeat 4 { [set n (add n 1)] [clone] }] } |clone| { [set g (add g n)] [wait 2] [delete_clone] } } }
+        actor B { data { (x = 0) } events { |start| { [request x ask 1 timeout 2] [set g (add g 1)] [broadcast hit] } } }
+        actor C { data { (n = 0) } events { |start| { [loop { [set n (add n 1)] [wait 1] }] } |hit| { [set n (add n 100)] } } }
+        actor D { data { (n = 0) } events { |start| { [repeat 3 { [set n (add n 1)] }] [set n (mod n 0)] } } }
+    "#;
+    // the variables after every step, with what the step returned
+    let steps = |runtime: &mut Runtime<'static>, step: &mut dyn FnMut(&mut Runtime<'static>) -> Result<bool, Error>| {
+        runtime.start().unwrap();
+        (0..12).map(|_| {
+            let result = step(runtime).map_err(|error| located(&error));
+            (result, runtime.interpreter.variables().into_iter().map(|(_, value)| value.to_string()).collect::<Vec<String>>())
+        }).collect::<Vec<_>>()
+    };
+    let cooperative = steps(&mut compile(text, Box::new(RoundRobin)).unwrap(), &mut |runtime| runtime.step());
+    // D fails in the fourth tick, the steps after it go on with the other actors
+    assert_eq!(cooperative[3].0, Err("7:95: modulo by zero".to_string()));
+    assert!(cooperative[4..].iter().all(|(result, _)| result == &Ok(true)));
+    for threads in 1..=4 {
+        let mut runtime = compile(text, Box::new(RoundRobin)).unwrap().threaded(threads);
+        let threaded = std::thread::scope(|scope| {
+            let pool = runtime.pool(scope).unwrap();
+            steps(&mut runtime, &mut |runtime| runtime.step_on(pool.as_ref()))
+        });
+        assert_eq!(threaded, cooperative, "{threads} threads");
+    }
+}
+
+#[test]
+fn clones_copy_the_variables_of_their_parent() {
+    let text = r#"
+        data { (total = 0) (log = "") }
+        actor A {
+            data { (n = 0) }
+            events {
+                |start| { [repeat 3 { [set n (add n 1)] [clone] }] }
+                |clone| { [set n (mul n 10)] [set total (add total n)] }
+            }
+        }
+        actor B { events { |start| { [wait 5] [clone A] } } }
+    "#;
+    assert_eq!(run(text, 20, "total"), "90");
+    assert_eq!(run(text, 20, "A.n"), "3");
+    assert_same_in_every_mode(text, 20);
+}
+
+#[test]
+fn clones_copy_the_variables_when_they_are_requested() {
+    let text = r#"
+        data { (total = 0) }
+        actor B { events { |start| { [clone A] } } }
+        actor A {
+            data { (n = 1) }
+            events {
+                |start| { [set n 2] [delete_clone] [set n 3] }
+                |clone| { [set total (add total n)] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 10, "total"), "1");
+    // `[delete_clone]` does nothing in the actor itself
+    assert_eq!(run(text, 10, "A.n"), "3");
+    assert_same_in_every_mode(text, 10);
+}
+
+#[test]
+fn deleted_clones_stop_every_script() {
+    let text = r#"
+        data { (log = "") }
+        messages { (ping) }
+        actor A {
+            events {
+                |start| { [clone] [wait 2] [broadcast ping] [delete_clone] [set log (join log "a")] }
+                |clone| { [loop { [set log (join log "c")] }] }
+                |clone| { [wait 1] [delete_clone] }
+                |ping| { [set log (join log "p")] }
+            }
+        }
+    "#;
+    assert_eq!(run(text, 20, "log"), "ccacp");
+}
+
+#[test]
+fn clones_are_limited() {
+    let text = r#"
+        actor A { events { |start| { [loop { [clone] }] } |clone| { [wait 3] [delete_clone] } } }
+    "#;
+    let limited = |max_clones| compile(text, Box::new(RoundRobin)).unwrap().max_clones(max_clones).run(Some(20));
+    // a clone is freed at the start of the fifth tick after it started
+    assert!(limited(5).is_ok());
+    // the position of `[clone]`
+    assert_eq!(located(&limited(4).unwrap_err()), "2:46: maximum of 4 clones exceeded");
+}
+
+#[test]
+fn expressions_evaluate() {
+    let text = r#"
+        data { (a) (b) (c) (d) (e) (f) (g) (h) (i) (j) (k) (l) }
+        actor A {
+            functions { #(sum) (n) { [if <eq n 0> { [return 0] }] [return (add n (sum (sub n 1)))] } }
+            events { |start| {
+                [set a (mod (sub 0 7) 3)] [set b (mod 7 (sub 0 3))] [set c (div 5 2)] [set d (pow 2 10)]
+                [set e (max 1 (min 5 3))] [set f (index "häö" 2)] [set g (substring "häöx" 2 3)] [set h (substring "abc" 3 2)]
+                [set i (add 1 0.5)] [set j (sum 20)] [set k (contains "abc" "bc")] [set l <and <lt "a" "b"> <not <eq 1 2>>>]
+            } }
+        }
+    "#;
+    let values = variables(text, 5, Box::new(RoundRobin), None).into_iter().map(|(_, value)| value).collect::<Vec<String>>();
+    // `mod` takes the sign of the divisor, indices count chars
+    assert_eq!(values, vec!["2", "-2", "2.5", "1024", "3", "ä", "äö", "", "1.5", "210", "true", "true"]);
+}
+
+#[test]
+fn runtime_errors_point_at_the_expression() {
+    assert_eq!(error("actor A { events { |start| { [wait (div 1 0)] } } }", 5), "1:36: division by zero");
+    assert_eq!(error("actor A { events { |start| { [wait (mod 1 (sub 1 1))] } } }", 5), "1:36: modulo by zero");
+    assert_eq!(error("data { (s) } actor A { events { |start| { [wait 0] [set s (index \"ab\" 3)] } } }", 5), "1:71: index 3 out of range for string of length 2");
+    assert_eq!(error("data { (s) } actor A { events { |start| { [set s (index \"äb\" 1.5)] } } }", 5), "1:62: index 1.5 out of range for string of length 2");
+    assert_eq!(error("data { (s) } actor A { events { |start| { [set s (substring \"ab\" 0 1)] } } }", 5), "1:66: index 0 out of range for string of length 2");
+}
+
+#[test]
+fn runaway_recursion_is_stopped() {
+    let functions = r#"
+        data { (r) }
+        actor A {
+            functions { #(sum) (n) { [if <eq n 0> { [return 0] }] [return (add n (sum (sub n 1)))] } #(f) (n) (add (f n) 1) }
+            events { |start| { [set r (sum 255)] } |start| { [wait 1] [set r (f 1)] } }
+        }
+    "#;
+    let procedures = "actor A { procedures { #p { [p] } } events { |start| { [p] } } }";
+    // scripts run on threads with a stack of `STACK_SIZE`
+    let (sum, errors) = std::thread::scope(|scope| std::thread::Builder::new().stack_size(STACK_SIZE)
+        .spawn_scoped(scope, || (run(functions, 1, "r"), [error(functions, 5), error(procedures, 5)]))
+        .unwrap().join().unwrap());
+    assert_eq!(sum, "32640");
+    assert_eq!(errors, [format!("4:116: maximum call depth of {MAX_DEPTH} exceeded"), format!("1:29: maximum call depth of {MAX_DEPTH} exceeded")]);
+}
+
+#[test]
+fn bracketed_variables_are_not_calls() {
+    let text = r#"
+        data { (g = 2) (h = (g)) (log = "") }
+        actor A {
+            data { (x = 3) (y = 0) (z = 0) }
+            functions { #(x2) (n) (mul (n) 2) #(h) ("function") }
+            events { |start| { [set y (add (x) (x2 (g)))] [if <eq (x) 3> { [set z (x2 (y))] }] [set log (h)] } }
+        }
+    "#;
+    assert_eq!(run(text, 10, "h"), "2");
+    assert_eq!(run(text, 10, "A.y"), "7");
+    assert_eq!(run(text, 10, "A.z"), "14");
+    // functions win over variables of the same name
+    assert_eq!(run(text, 10, "log"), "function");
+}
+
+#[test]
+fn step_runs_one_tick() {
+    let text = r#"
+        data { (n = 0) (m = 0) (w = 0) }
+        actor A {
+            events {
+                |start| { [repeat 3 { [set n (add n 1)] }] [set m 1] [while <lt m 3> { [set m (add m 1)] }] }
+                |start| { [wait 2] [set w 1] [loop { [break] }] [set w 2] }
+            }
+        }
+    "#;
+    let mut runtime = compile(text, Box::new(RoundRobin)).unwrap();
+    runtime.start().unwrap();
+    let mut ticks = vec![];
+    loop {
+        let running = runtime.step().unwrap();
+        let values = runtime.interpreter.variables().into_iter().map(|(_, value)| value.to_string()).collect::<Vec<String>>();
+        ticks.push(format!("{} {running}", values.join(" ")));
+        if !running { break; }
+    }
+    // every iteration ends the tick, also the last one, leaving a loop with `[break]` doesn't
+    assert_eq!(ticks, vec!["1 0 0 true", "2 0 0 true", "3 0 0 true", "3 2 2 true", "3 3 2 true", "3 3 2 false"]);
+}
@@ -0,0 +1,212 @@
+use crate::{location::position::Located, error::Error, parser::ast::*};
+use super::{value::Value, interpreter::{Interpreter, MAX_DEPTH}, message::{Broadcast, Mail, Request}};
+
+// what is left to do in a thread, the innermost frame is the last one
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    // statements of a block and the index of the next one
+    Block(&'a [Located<Statement>], usize),
+    // iterations left
+    Repeat(f64, &'a Located<Block>),
+    While(&'a Located<Condition>, &'a Located<Block>),
+    Loop(&'a Located<Block>),
+    // a procedure call, its parameters are the last ones of the thread
+    Call,
+}
+impl<'a> Frame<'a> {
+    pub fn block(block: &'a Located<Block>) -> Self {
+        Self::Block(&block.item.statements, 0)
+    }
+    pub fn is_loop(&self) -> bool {
+        matches!(self, Self::Repeat(_, _) | Self::While(_, _) | Self::Loop(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Yielded, Finished,
+    // the event ended with `[return]`
+    Returned(Value),
+    // the clone running the event deleted itself
+    Deleted
+}
+
+// a green thread running the body of an event, it can be paused between any two statements
+#[derive(Debug, Clone)]
+pub struct Thread<'a> {
+    pub id: usize,
+    // actor and index into `Actor::events`
+    pub event: (usize, usize),
+    // the actor or clone the event belongs to
+    pub instance: usize,
+    frames: Vec<Frame<'a>>,
+    // parameters of every procedure call in progress, the event's bindings first
+    params: Vec<Vec<Value>>,
+    // ticks left before the thread continues
+    pub sleep: usize,
+    // ids of the handlers of a broadcast the thread waits for, filled in once the broadcast is delivered
+    pub awaiting: Option<Vec<usize>>,
+    // mail sent since the runtime last collected it
+    pub outbox: Vec<Mail>,
+    // the request the thread waits on a reply for
+    pub request: Option<Request<'a>>,
+    // number of requests sent so far
+    requests: usize,
+    // thread and request id a `[return]` replies to
+    pub reply_to: Option<(usize, usize)>
+}
+impl<'a> Thread<'a> {
+    pub fn new(id: usize, event: (usize, usize), instance: usize, body: &'a Located<Block>, bindings: Vec<Value>) -> Self {
+        Self {
+            id, event, instance, frames: vec![Frame::block(body)], params: vec![bindings], sleep: 0,
+            awaiting: None, outbox: vec![], request: None, requests: 0, reply_to: None
+        }
+    }
+    // starts the event over, keeping the id and place of the thread,
+    // request ids keep counting so replies to an old request are ignored
+    pub fn restart(&mut self, body: &'a Located<Block>, bindings: Vec<Value>) {
+        let requests = self.requests;
+        *self = Self::new(self.id, self.event, self.instance, body, bindings);
+        self.requests = requests;
+    }
+    pub fn params(&self) -> &[Value] {
+        self.params.last().map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // runs until the end of a loop iteration, a wait or the end of the event
+    pub fn step(&mut self, interpreter: &mut Interpreter<'a>) -> Result<Status, Error> {
+        interpreter.instance = self.instance;
+        while let Some(frame) = self.frames.pop() {
+            match frame {
+                Frame::Block(statements, idx) => match statements.get(idx) {
+                    Some(stat) => {
+                        self.frames.push(Frame::Block(statements, idx + 1));
+                        if let Some(status) = self.statement(stat, interpreter)? {
+                            return Ok(status)
+                        }
+                    }
+                    // every iteration of a loop ends by yielding
+                    None => if self.frames.last().is_some_and(Frame::is_loop) {
+                        return Ok(Status::Yielded)
+                    }
+                }
+                Frame::Repeat(count, body) => if count > 0. {
+                    self.frames.push(Frame::Repeat(count - 1., body));
+                    self.frames.push(Frame::block(body));
+                }
+                Frame::While(cond, body) => if interpreter.condition(cond, self.params())? {
+                    self.frames.push(frame);
+                    self.frames.push(Frame::block(body));
+                }
+                Frame::Loop(body) => {
+                    self.frames.push(frame);
+                    self.frames.push(Frame::block(body));
+                }
+                Frame::Call => {
+                    self.params.pop();
+                }
+            }
+        }
+        Ok(Status::Finished)
+    }
+    // statements with blocks push frames instead of running them, everything else runs right away
+    pub fn args(&self, args: &[Argument], interpreter: &mut Interpreter<'a>) -> Result<Vec<Value>, Error> {
+        args.iter().map(|arg| interpreter.argument(arg, self.params())).collect()
+    }
+    pub fn statement(&mut self, stat: &'a Located<Statement>, interpreter: &mut Interpreter<'a>) -> Result<Option<Status>, Error> {
+        match &stat.item {
+            Statement::If(cond, case) => if interpreter.condition(cond, self.params())? {
+                self.frames.push(Frame::block(case));
+            }
+            Statement::IfElse(cond, case, else_case) => {
+                let case = if interpreter.condition(cond, self.params())? { case } else { else_case };
+                self.frames.push(Frame::block(case));
+            }
+            // the count is rounded to the nearest whole number, like in Scratch
+            Statement::Repeat(count, body) => {
+                let count = interpreter.number(count, self.params())?.round();
+                self.frames.push(Frame::Repeat(count, body));
+            }
+            Statement::While(cond, body) => self.frames.push(Frame::While(cond, body)),
+            Statement::Loop(body) => self.frames.push(Frame::Loop(body)),
+            Statement::Break => while let Some(frame) = self.frames.pop() {
+                if frame.is_loop() { break }
+            }
+            Statement::Next => {
+                while self.frames.last().is_some_and(|frame| !frame.is_loop()) {
+                    self.frames.pop();
+                }
+                return Ok(Some(Status::Yielded))
+            }
+            Statement::Call(id, args) => {
+                let procedure = interpreter.procedure(id)?;
+                let values = interpreter.arguments(&procedure.params, args, self.params(), &stat.pos)?;
+                if self.params.len() > MAX_DEPTH {
+                    return Err(interpreter.error(format!("maximum call depth of {MAX_DEPTH} exceeded"), &stat.pos))
+                }
+                self.params.push(values);
+                self.frames.push(Frame::Call);
+                self.frames.push(Frame::block(&procedure.body));
+            }
+            // `[wait 0]` only yields, like in Scratch
+            Statement::Wait(ticks) => {
+                let ticks = interpreter.number(ticks, self.params())?.round().max(0.);
+                self.sleep = ticks as usize;
+                return Ok(Some(Status::Yielded))
+            }
+            Statement::Broadcast(id, args) => {
+                let args = self.args(args, interpreter)?;
+                self.outbox.push(Mail::Broadcast(Broadcast::new(interpreter.message(id)?, args, None, None)));
+            }
+            Statement::BroadcastWait(id, args) => {
+                let args = self.args(args, interpreter)?;
+                self.outbox.push(Mail::Broadcast(Broadcast::new(interpreter.message(id)?, args, Some(self.id), None)));
+                self.awaiting = Some(vec![]);
+                return Ok(Some(Status::Yielded))
+            }
+            Statement::Request(var, id, args, timeout) => {
+                let args = self.args(args, interpreter)?;
+                let ticks = match timeout {
+                    Some(timeout) => Some(interpreter.number(timeout, self.params())?.round().max(0.) as usize),
+                    None => None
+                };
+                self.outbox.push(Mail::Broadcast(Broadcast::new(interpreter.message(id)?, args, Some(self.id), Some(self.requests))));
+                self.request = Some(Request::new(self.requests, var, ticks));
+                self.requests += 1;
+                self.awaiting = Some(vec![]);
+                return Ok(Some(Status::Yielded))
+            }
+            // a clone starts with the current variables of the actor or of the running clone, like in Scratch
+            Statement::Clone(id) => {
+                let (actor, instance) = match id {
+                    Some(id) => {
+                        let actor = interpreter.actor(id)?;
+                        (actor, actor)
+                    }
+                    None => (self.event.0, self.instance)
+                };
+                self.outbox.push(Mail::Clone(actor, interpreter.locals[instance].clone(), stat.pos.clone()));
+            }
+            // the actors themselves can't be deleted, in them it does nothing like in Scratch
+            Statement::DeleteClone => if self.instance >= interpreter.program.actors.len() {
+                self.frames.clear();
+                return Ok(Some(Status::Deleted))
+            }
+            // only events run as threads, so this ends the event
+            Statement::Return(expr) => {
+                let value = interpreter.expression(expr, self.params())?;
+                self.frames.clear();
+                return Ok(Some(Status::Returned(value)))
+            }
+            Statement::ReturnCondition(cond) => {
+                let value = Value::Bool(interpreter.condition(cond, self.params())?);
+                self.frames.clear();
+                return Ok(Some(Status::Returned(value)))
+            }
+            _ => {
+                interpreter.statement(stat, self.params())?;
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,33 @@
+use std::fmt::Display;
+use crate::parser::ast::Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64), String(String), Bool(bool)
+}
+impl Value {
+    // the value a variable of that type holds before it is set
+    pub fn default(typ: Type) -> Self {
+        match typ {
+            Type::Number => Self::Number(0.),
+            Type::String => Self::String(String::new()),
+            Type::Bool => Self::Bool(false),
+        }
+    }
+    pub fn typ(&self) -> Type {
+        match self {
+            Self::Number(_) => Type::Number,
+            Self::String(_) => Type::String,
+            Self::Bool(_) => Type::Bool,
+        }
+    }
+}
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::String(string) => write!(f, "{string}"),
+            Self::Bool(bool) => write!(f, "{bool}"),
+        }
+    }
+}
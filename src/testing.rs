@@ -0,0 +1,6 @@
+use crate::error::Error;
+
+// an error as `LINE:COLUMN: MESSAGE`, counted from 1, without the path of the input
+pub fn located(error: &Error) -> String {
+    error.to_string().trim_start_matches("<input>:").to_string()
+}